@@ -0,0 +1,214 @@
+//! Error types
+
+use num_derive::FromPrimitive;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use thiserror::Error;
+
+/// Errors that may be returned by the will program.
+///
+/// The discriminants are the `ProgramError::Custom` codes seen by clients, so
+/// existing variants must never be renumbered.
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum WillError {
+    /// The dead-man timer has not expired yet.
+    #[error("Will is not released yet")]
+    NotYetReleased = 1,
+    /// The sender holds no unclaimed share in the will.
+    #[error("Sender is not an inheritor")]
+    NotAnInheritor = 2,
+    /// Every share in the will has already been claimed, or none was set.
+    #[error("No shares left to claim")]
+    TotalSharesZero = 3,
+    /// A withdrawal would take the will below its rent-exempt minimum.
+    #[error("Withdrawal would leave the will below the rent-exempt minimum")]
+    BelowRentExemptReserve = 4,
+    /// A lamport balance would overflow.
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow = 5,
+    /// Inheritor names, keys and shares were not given one per inheritor.
+    #[error("Inheritor names, pubkeys and shares differ in length")]
+    InheritorsLengthMismatch = 6,
+    /// Inheritor shares add up to more than `MAX_TOTAL_SHARES`.
+    #[error("Inheritor shares exceed the total available")]
+    ShareSumExceeded = 7,
+    /// The dead-man timeout is outside of the allowed bounds.
+    #[error("Timeout is out of bounds")]
+    TimeoutOutOfBounds = 8,
+    /// The will is already stored in the current layout.
+    #[error("Will is already migrated")]
+    AlreadyMigrated = 9,
+    /// The dead-man timer has expired, the will can not be changed until the owner checks in.
+    #[error("Will is already released")]
+    AlreadyReleased = 10,
+    /// The sender is not the owner stored in the will.
+    #[error("Sender is not the owner")]
+    NotTheOwner = 11,
+    /// The vesting duration is negative or longer than the longest timeout.
+    #[error("Vesting is out of bounds")]
+    VestingOutOfBounds = 12,
+    /// More inheritors were listed than `MAX_INHERITORS`.
+    #[error("Too many inheritors")]
+    TooManyInheritors = 13,
+    /// An inheritor was listed with a share of zero.
+    #[error("Inheritor share is zero")]
+    ZeroShare = 14,
+    /// The owner listed themselves as an inheritor.
+    #[error("Owner can not be an inheritor")]
+    OwnerIsInheritor = 15,
+    /// The will is frozen, no lamports or tokens may leave it.
+    #[error("Will is frozen")]
+    Frozen = 16,
+    /// The sender is not the executor of the will.
+    #[error("Sender is not the executor")]
+    NotTheExecutor = 17,
+    /// The sender is not a guardian of the will.
+    #[error("Sender is not a guardian")]
+    NotAGuardian = 18,
+    /// The guardian already confirmed the death of the owner.
+    #[error("Guardian already confirmed")]
+    AlreadyConfirmed = 19,
+    /// More guardians were listed than `MAX_GUARDIANS`.
+    #[error("Too many guardians")]
+    TooManyGuardians = 20,
+    /// More confirmations are required than there are distinct guardians.
+    #[error("Required confirmations are out of bounds")]
+    ConfirmationsOutOfBounds = 21,
+    /// The will account was passed as its own inheritor.
+    #[error("Sender can not be the will itself")]
+    SenderIsWill = 22,
+    /// The sender is not a wallet owned by the System Program.
+    #[error("Sender is not a wallet")]
+    SenderNotAWallet = 23,
+    /// An inheritor name is longer than `MAX_NAME_BYTES`.
+    #[error("Inheritor name is too long")]
+    NameTooLong = 24,
+    /// An inheritor name contains control characters, which break displaying it.
+    #[error("Inheritor name contains control characters")]
+    NameHasControlCharacters = 25,
+    /// No inheritor is listed at the given index.
+    #[error("Inheritor index is out of bounds")]
+    InheritorIndexOutOfBounds = 26,
+    /// The will was written by a newer version of the program.
+    #[error("Will schema version is not supported")]
+    UnsupportedSchemaVersion = 27,
+    /// The sender claims for an inheritor who did not have them as a delegate.
+    #[error("Sender is not the inheritor's delegate")]
+    NotTheDelegate = 28,
+    /// The inheritor already voted to extend the release.
+    #[error("Inheritor already voted")]
+    AlreadyVoted = 29,
+    /// The account is not the fallback of the will, or the will names none.
+    #[error("Account is not the fallback")]
+    NotTheFallback = 30,
+    /// The will has not stayed unclaimed long enough to be swept to its fallback.
+    #[error("Fallback sweep is not due yet")]
+    FallbackNotDue = 31,
+    /// The will's `min_claim_interval` did not pass since its last throttled instruction.
+    #[error("Too soon after the last action on the will")]
+    ActionTooSoon = 32,
+    /// The withdrawal would take more than `max_withdraw_per_period` within the period.
+    #[error("Withdrawal exceeds the cap for the period")]
+    WithdrawCapExceeded = 33,
+    /// `Pubkey::create_with_seed` can not derive a will address from the sender's key.
+    #[error("Will address can not be derived")]
+    WillAddressUnderivable = 34,
+    /// The will is not at the address derived from the sender's key.
+    #[error("Will is not at the sender's will address")]
+    WillAddressMismatch = 35,
+    /// A claim asked for more lamports than the inheritor may claim now.
+    #[error("Claim exceeds the entitlement")]
+    ClaimExceedsEntitlement = 36,
+    /// The sender is listed in the will, but every share they held was paid out.
+    #[error("Share was already claimed")]
+    AlreadyClaimed = 37,
+    /// A contingent names itself or a missing inheritor, an inheritor would have two,
+    /// or a contingent would have one of their own.
+    #[error("Invalid contingent inheritor")]
+    InvalidContingent = 38,
+    /// The sender's share passed to their contingent, see `WillData::lapsed`.
+    #[error("Share lapsed to the contingent inheritor")]
+    ShareLapsed = 39,
+    /// More will token accounts were listed than `MAX_TOKEN_ACCOUNTS`.
+    #[error("Too many token accounts")]
+    TooManyTokenAccounts = 40,
+    /// A claim passed a token account the will does not list, see `WillData::token_accounts`.
+    #[error("Token account is not listed in the will")]
+    TokenAccountNotListed = 41,
+    /// The inheritors already pushed the release back `MAX_EXTENSIONS` times.
+    #[error("Too many extensions")]
+    TooManyExtensions = 42,
+}
+
+impl From<WillError> for ProgramError {
+    fn from(e: WillError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for WillError {
+    fn type_of() -> &'static str {
+        "WillError"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(code: u32) -> Option<WillError> {
+        WillError::decode_custom_error_to_enum(code)
+    }
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        let codes = [
+            (WillError::NotYetReleased, 1),
+            (WillError::NotAnInheritor, 2),
+            (WillError::TotalSharesZero, 3),
+            (WillError::BelowRentExemptReserve, 4),
+            (WillError::ArithmeticOverflow, 5),
+            (WillError::InheritorsLengthMismatch, 6),
+            (WillError::ShareSumExceeded, 7),
+            (WillError::TimeoutOutOfBounds, 8),
+            (WillError::AlreadyMigrated, 9),
+            (WillError::AlreadyReleased, 10),
+            (WillError::NotTheOwner, 11),
+            (WillError::VestingOutOfBounds, 12),
+            (WillError::TooManyInheritors, 13),
+            (WillError::ZeroShare, 14),
+            (WillError::OwnerIsInheritor, 15),
+            (WillError::Frozen, 16),
+            (WillError::NotTheExecutor, 17),
+            (WillError::NotAGuardian, 18),
+            (WillError::AlreadyConfirmed, 19),
+            (WillError::TooManyGuardians, 20),
+            (WillError::ConfirmationsOutOfBounds, 21),
+            (WillError::SenderIsWill, 22),
+            (WillError::SenderNotAWallet, 23),
+            (WillError::NameTooLong, 24),
+            (WillError::NameHasControlCharacters, 25),
+            (WillError::InheritorIndexOutOfBounds, 26),
+            (WillError::UnsupportedSchemaVersion, 27),
+            (WillError::NotTheDelegate, 28),
+            (WillError::AlreadyVoted, 29),
+            (WillError::NotTheFallback, 30),
+            (WillError::FallbackNotDue, 31),
+            (WillError::ActionTooSoon, 32),
+            (WillError::WithdrawCapExceeded, 33),
+            (WillError::WillAddressUnderivable, 34),
+            (WillError::WillAddressMismatch, 35),
+            (WillError::ClaimExceedsEntitlement, 36),
+            (WillError::AlreadyClaimed, 37),
+            (WillError::InvalidContingent, 38),
+            (WillError::ShareLapsed, 39),
+            (WillError::TooManyTokenAccounts, 40),
+            (WillError::TokenAccountNotListed, 41),
+            (WillError::TooManyExtensions, 42),
+        ];
+        for (error, code) in codes.iter() {
+            assert_eq!(ProgramError::from(error.clone()), ProgramError::Custom(*code));
+            assert_eq!(decode(*code).as_ref(), Some(error));
+        }
+        assert_eq!(decode(0), None);
+    }
+}
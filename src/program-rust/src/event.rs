@@ -0,0 +1,103 @@
+//! Events logged for indexers
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{clock::UnixTimestamp, entrypoint::ProgramResult, log::sol_log_data, pubkey::Pubkey};
+
+/// Binary events logged by every instruction that changes a will.
+///
+/// Logged borsh-encoded through `sol_log_data`, so they show up base64 encoded
+/// as "Program data:" in the transaction logs. Indexers decode them by variant
+/// index, so variants are only ever appended.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum WillEvent {
+    /// The owner replaced the inheritors.
+    InheritorsUpdated {
+        will: Pubkey,
+        inheritors: Vec<Pubkey>,
+        shares: Vec<u32>,
+        max_total_shares: u32,
+        withdraw_allowed_ts: UnixTimestamp,
+    },
+    /// The owner withdrew their own lamports.
+    SolWithdrawn { will: Pubkey, lamports: u64 },
+    /// An inheritor was paid out.
+    Claimed { will: Pubkey, inheritor: Pubkey, lamports: u64 },
+    /// The owner deposited lamports.
+    Deposited { will: Pubkey, lamports: u64 },
+    /// The owner initialized a new will.
+    Initialized { will: Pubkey, owner: Pubkey },
+    /// The owner restarted the dead-man timer.
+    Heartbeat { will: Pubkey, withdraw_allowed_ts: UnixTimestamp },
+    /// The will was rewritten from an older layout.
+    Migrated { will: Pubkey, from_schema_version: u8 },
+    /// The owner revoked the will and took back its lamports.
+    Revoked { will: Pubkey, lamports: u64 },
+    /// The owner froze or unfroze the will.
+    FrozenChanged { will: Pubkey, frozen: bool },
+    /// The owner appointed or removed the executor.
+    ExecutorChanged { will: Pubkey, executor: Option<Pubkey> },
+    /// The owner replaced the guardians.
+    GuardiansUpdated { will: Pubkey, guardians: Vec<Pubkey>, required_confirmations: u8 },
+    /// A guardian confirmed the death of the owner.
+    DeathConfirmed { will: Pubkey, guardian: Pubkey, confirmations: u8 },
+    /// The executor or the guardians released the will before its timeout.
+    Released { will: Pubkey, withdraw_allowed_ts: UnixTimestamp },
+    /// The last claim emptied the will, `recipient` received the lamports left in it.
+    Closed { will: Pubkey, recipient: Pubkey, lamports: u64 },
+    /// The owner appointed or removed the delegate of an inheritor.
+    DelegateChanged { will: Pubkey, inheritor: Pubkey, delegate: Option<Pubkey> },
+    /// An inheritor voted to push the release back.
+    ExtensionVoted { will: Pubkey, inheritor: Pubkey, votes: u8 },
+    /// A quorum of inheritors pushed the release back.
+    ReleaseExtended { will: Pubkey, withdraw_allowed_ts: UnixTimestamp },
+    /// The owner named or removed the fallback.
+    FallbackChanged { will: Pubkey, fallback: Option<Pubkey> },
+    /// What the inheritors left unclaimed went to the fallback, closing the will.
+    SweptToFallback { will: Pubkey, fallback: Pubkey, lamports: u64 },
+    /// The owner handed the will to a new owner.
+    OwnershipTransferred { will: Pubkey, previous_owner: Pubkey, new_owner: Pubkey },
+    /// The owner changed how far apart owner instructions and claims must be.
+    MinClaimIntervalChanged { will: Pubkey, seconds: i64 },
+    /// The owner set or removed the fixed token amount of an inheritor, along with its mint.
+    FixedAmountChanged { will: Pubkey, inheritor: Pubkey, fixed_amount: Option<(Pubkey, u64)> },
+    /// The owner set or removed the cap on withdrawals per period, which applies from
+    /// `effective_ts` on.
+    WithdrawCapChanged { will: Pubkey, max_withdraw_per_period: Option<u64>, effective_ts: UnixTimestamp },
+    /// The owner named or removed the inheritor whose share `inheritor` takes if
+    /// they lapse.
+    ContingentChanged { will: Pubkey, inheritor: Pubkey, contingent_of: Option<Pubkey> },
+    /// An inheritor let `CONTINGENCY_SECONDS` pass without claiming, the first claim
+    /// afterwards recorded that their share passed to their contingent.
+    ShareLapsed { will: Pubkey, inheritor: Pubkey, contingent: Pubkey },
+    /// The owner listed a token account held by the will's token authority.
+    TokenAccountAdded { will: Pubkey, token_account: Pubkey },
+}
+
+/// Logs `event` for indexers.
+pub fn emit_event(event: &WillEvent) -> ProgramResult {
+    sol_log_data(&[&event.try_to_vec()?]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_event_encoding_is_stable() {
+        let will = Pubkey::new_unique();
+        let event = WillEvent::SolWithdrawn { will, lamports: 1_000 };
+        let mut expected = vec![1];
+        expected.extend_from_slice(will.as_ref());
+        expected.extend_from_slice(&1_000_u64.to_le_bytes());
+        assert_eq!(event.try_to_vec().unwrap(), expected);
+        assert_eq!(WillEvent::try_from_slice(&expected).unwrap(), event);
+
+        let inheritor = Pubkey::new_unique();
+        let event = WillEvent::Claimed { will, inheritor, lamports: 7 };
+        let bytes = event.try_to_vec().unwrap();
+        assert_eq!(bytes[0], 2);
+        assert_eq!(WillEvent::try_from_slice(&bytes).unwrap(), event);
+        assert_eq!(WillEvent::Released { will, withdraw_allowed_ts: 0 }.try_to_vec().unwrap()[0], 12);
+    }
+}
@@ -0,0 +1,731 @@
+//! Instruction types
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program,
+};
+
+use crate::{check_timeout, error::WillError, find_token_authority, MAX_INHERITORS, MAX_NAME_BYTES, MAX_TIMEOUT_SECONDS};
+
+/// Instructions supported by the will program.
+///
+/// Encoded with borsh, so the first byte of the instruction data is the selector,
+/// the index of the variant. Every instruction takes the sender followed by the
+/// will account.
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub enum WillInstruction {
+    /// 0 - replace the inheritors, timeout and vesting of the sender's will, with
+    /// shares in basis points. Shares need not add up to 10000, they are weights.
+    SetInheritance(SetInheritenceMessage),
+    /// 1 - withdraw the owner's own lamports, restarting the dead-man timer unless
+    /// `refresh_timer` is false.
+    WithdrawSol(WithdrawSolMessage),
+    /// 2 - withdraw the sender's inheritance, into the optional destination account
+    /// after the will, see `ClaimMessage`, or else the sender. The last inheritor to claim also receives
+    /// the lamports left over from rounding the others down, and the emptied will is
+    /// closed. A delegate claims on an inheritor's behalf by passing the inheritor as
+    /// the destination, even when they are an inheritor too, a delegate is never paid
+    /// themselves. A will listing token accounts keeps the share open for a
+    /// `ClaimWithTokens` paying them out.
+    Claim(ClaimMessage),
+    /// 3 - initialize a freshly created will account.
+    Initialize { timeout_seconds: i64 },
+    /// 4 - heartbeat, the owner proves they are alive without moving funds.
+    Heartbeat,
+    /// 5 - withdraw the sender's inheritance along with the same share of the will's
+    /// SPL tokens. Takes the SPL Token program, the will's token authority and pairs
+    /// of (will token account, inheritor token account) after the will, each will
+    /// token account listed in the will and passed once. Tokens are paid once the
+    /// share completes, which takes every token account the will lists.
+    ClaimWithTokens,
+    /// 6 - log the lamports a claim by the sender would pay, without changing anything.
+    PreviewClaim,
+    /// 7 - rewrite a will stored in an older layout in the current one.
+    Migrate,
+    /// 8 - revoke the will, returning every lamport to the owner and closing the account.
+    /// A will listing token accounts also takes the SPL Token program, the will's token
+    /// authority and a (will token account, owner token account) pair for each listed
+    /// token account, in the order the will lists them, and empties them to the owner.
+    Revoke,
+    /// 9 - freeze or unfreeze the will, a frozen will pays out neither withdrawals nor claims.
+    /// A released will may only be unfrozen.
+    SetFrozen { frozen: bool },
+    /// 10 - appoint the executor of the will, or remove them with `None`.
+    SetExecutor { executor: Option<Pubkey> },
+    /// 11 - the executor releases the will now, without waiting for the timeout.
+    Execute,
+    /// 12 - a guardian confirms the death of the owner, once `required_confirmations`
+    /// guardians did the will is released without waiting for the timeout.
+    ConfirmDeath,
+    /// 13 - replace the guardians of the will and the confirmations they need to
+    /// release it, 0 confirmations leave the release to the timeout alone.
+    SetGuardians { guardians: Vec<Pubkey>, required_confirmations: u8 },
+    /// 14 - the owner deposits lamports into the will, which also restarts the
+    /// dead-man timer. Takes the System Program after the will.
+    Deposit { lamports: u64 },
+    /// 15 - like `SetInheritance`, with shares in parts per million.
+    SetInheritancePpm(SetInheritenceMessage<u32>),
+    /// 16 - change the share of the inheritor at `index`, counted in the will's
+    /// current unit, basis points or parts per million.
+    UpdateInheritor { index: u8, new_share: u32 },
+    /// 17 - list one more inheritor, with a share in the will's current unit.
+    AddInheritor { name: String, pubkey: Pubkey, share: u32 },
+    /// 18 - remove the inheritor at `index`.
+    RemoveInheritor { index: u8 },
+    /// 19 - let `delegate` claim for the inheritor at `index`, such as a guardian
+    /// of a minor heir, or stop them with `None`.
+    SetDelegate { index: u8, delegate: Option<Pubkey> },
+    /// 20 - an inheritor votes to delay the distribution, say during a probate
+    /// dispute. Once more than half of the inheritors still holding a share voted,
+    /// the release moves `GRACE_EXTENSION_SECONDS` past the later of the current
+    /// release and now, and the votes start over. The release moves at most
+    /// `MAX_EXTENSIONS` times, and never reopens the will to the owner once an
+    /// inheritor claimed.
+    VoteExtension,
+    /// 21 - name who receives what the inheritors leave unclaimed, such as a
+    /// charity, or nobody with `None`.
+    SetFallback { fallback: Option<Pubkey> },
+    /// 22 - once the will stayed unclaimed for `FALLBACK_AFTER_SECONDS` after its
+    /// release, and its shares fully vested, anyone may sweep its lamports to the fallback, closing the will.
+    /// Takes the fallback after the will, then the token accounts like `Revoke`, with
+    /// the fallback holding the receiving token accounts.
+    SweepToFallback,
+    /// 23 - hand the will to `new_owner`, say after moving to a new wallet. The will
+    /// keeps its address, which was derived from the first owner's key, owner
+    /// instructions check the stored owner instead.
+    TransferOwnership { new_owner: Pubkey },
+    /// 24 - make the owner's instructions on the will come at least `seconds` apart,
+    /// at most `MAX_ACTION_INTERVAL_SECONDS`, against spam and double submits, 0 lifts
+    /// the limit.
+    SetMinClaimInterval { seconds: i64 },
+    /// 25 - pay the inheritor at `index` exactly the amount of `fixed_amount`, a
+    /// (mint, amount) pair, out of the will's token accounts of that mint before the
+    /// rest is split by share, such as 1000 USDC, or stop with `None`. Other mints are
+    /// only split by share. Fixed amounts exceeding a balance share it in proportion.
+    SetFixedAmount { index: u8, fixed_amount: Option<(Pubkey, u64)> },
+    /// 26 - return whether the will is released and when, as a `ReleaseStatus`
+    /// through the transaction's return data, without changing anything. Anyone may ask.
+    QueryReleaseStatus,
+    /// 27 - the executor pays every inheritor still holding a share what they may
+    /// claim, as if each claimed. Takes the account of every such inheritor after
+    /// the will, in any order, and pays everyone or nobody. Pays no tokens, so the
+    /// shares of a will listing token accounts stay open for `ClaimWithTokens`.
+    Distribute,
+    /// 28 - cap the lamports the owner may withdraw within `WITHDRAW_PERIOD_SECONDS`,
+    /// so a stolen owner key can not drain the will at once, or lift the cap with `None`.
+    /// A lower cap applies at once, a higher one or lifting it a period later.
+    SetWithdrawCap { max_withdraw_per_period: Option<u64> },
+    /// 29 - like `Initialize`, listing the inheritors of another will, a template,
+    /// which is taken read-only after the will.
+    InitializeFrom { timeout_seconds: i64 },
+    /// 30 - rename the inheritor at `index`, keeping their share and payouts.
+    RenameInheritor { index: u8, new_name: String },
+    /// 31 - make the inheritor at `index` the contingent of the inheritor at
+    /// `contingent_of`, who lapses to them after `CONTINGENCY_SECONDS` without a
+    /// claim, or make them nobody's contingent with `None`.
+    SetContingent { index: u8, contingent_of: Option<u8> },
+    /// 32 - list the token account after the will, held by the will's token
+    /// authority, so no share completes before a claim paid it out and its tokens
+    /// are not left behind when the will closes. At most `MAX_TOKEN_ACCOUNTS`.
+    AddTokenAccount,
+}
+
+impl WillInstruction {
+    /// Decodes instruction data. Bytes after the instruction are ignored.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        match data.first() {
+            None => {
+                msg!("Instruction data is empty, expected a selector byte");
+                Err(ProgramError::InvalidInstructionData)
+            }
+            Some(selector) => Self::deserialize(&mut &*data).map_err(|err| {
+                msg!("Malformed instruction data for selector {}: {}", selector, err);
+                ProgramError::InvalidInstructionData
+            }),
+        }
+    }
+}
+
+/// Lamports the owner withdraws, and whether the withdrawal restarts the dead-man timer.
+///
+/// `dry_run` checks the withdrawal and returns the lamports it would pay, but
+/// leaves the will as it is, so simulating it shows what the real one does.
+#[derive(BorshSerialize, Debug, PartialEq)]
+pub struct WithdrawSolMessage {
+    pub lamports: u64,
+    pub refresh_timer: bool,
+    pub dry_run: bool,
+}
+
+impl BorshDeserialize for WithdrawSolMessage {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        let lamports = u64::deserialize(buf)?;
+        // Clients from before the flag send the lamports alone, their withdrawals refresh the timer.
+        let refresh_timer = if buf.is_empty() { true } else { bool::deserialize(buf)? };
+        let dry_run = if buf.is_empty() { false } else { bool::deserialize(buf)? };
+        Ok(Self { lamports, refresh_timer, dry_run })
+    }
+}
+
+/// The account a claim pays out to, if it names one. It must then be passed after
+/// the will.
+///
+/// `amount` claims only that many lamports of what the inheritor may claim now,
+/// leaving the rest for later, say for tax timing. `None` claims all of it.
+///
+/// `dry_run` checks the claim and returns the lamports it would pay without paying
+/// them, see `WithdrawSolMessage`.
+#[derive(BorshSerialize, Debug, PartialEq)]
+pub struct ClaimMessage {
+    pub destination: Option<Pubkey>,
+    pub amount: Option<u64>,
+    pub dry_run: bool,
+}
+
+impl BorshDeserialize for ClaimMessage {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        // Clients from before the message send the selector alone, naming no destination,
+        // and clients from before partial claims stop after the destination.
+        let destination = if buf.is_empty() { None } else { Option::<Pubkey>::deserialize(buf)? };
+        let amount = if buf.is_empty() { None } else { Option::<u64>::deserialize(buf)? };
+        let dry_run = if buf.is_empty() { false } else { bool::deserialize(buf)? };
+        Ok(Self { destination, amount, dry_run })
+    }
+}
+
+/// Inheritors of a will, with shares counted in basis points (`u16`) or in parts
+/// per million (`u32`).
+#[derive(BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub struct SetInheritenceMessage<S = u16> {
+    pub inheritors_names: Vec<String>,
+    pub inheritors_pubkeys: Vec<Pubkey>,
+    pub inheritors_shares: Vec<S>,
+    pub timeout_seconds: i64,
+    pub vesting_seconds: i64,
+}
+
+impl<S: Copy + Into<u32>> SetInheritenceMessage<S> {
+    /// Checks the inheritors `owner` wants to list, whose shares may add up to
+    /// at most `max_total_shares`.
+    pub(crate) fn validate(&self, owner: &Pubkey, max_total_shares: u64) -> ProgramResult {
+        check_timeout(self.timeout_seconds)?;
+        if !(0..=MAX_TIMEOUT_SECONDS).contains(&self.vesting_seconds) {
+            msg!("Vesting of {} seconds is outside of [0, {}]", self.vesting_seconds, MAX_TIMEOUT_SECONDS);
+            return Err(WillError::VestingOutOfBounds.into());
+        }
+        if self.inheritors_names.len() != self.inheritors_pubkeys.len() ||
+                self.inheritors_pubkeys.len() != self.inheritors_shares.len() {
+            msg!("Got {} names, {} pubkeys and {} shares, expected one of each per inheritor",
+                self.inheritors_names.len(), self.inheritors_pubkeys.len(), self.inheritors_shares.len());
+            return Err(WillError::InheritorsLengthMismatch.into());
+        }
+        if self.inheritors_pubkeys.len() > MAX_INHERITORS {
+            msg!("Got {} inheritors, at most {} are allowed", self.inheritors_pubkeys.len(), MAX_INHERITORS);
+            return Err(WillError::TooManyInheritors.into());
+        }
+        if let Some(index) = self.inheritors_names.iter().position(|name| name.len() > MAX_NAME_BYTES) {
+            msg!("Inheritor {} has a name of {} bytes, at most {} are allowed", index, self.inheritors_names[index].len(), MAX_NAME_BYTES);
+            return Err(WillError::NameTooLong.into());
+        }
+        // Names are shown in wallets and logs, where null bytes, newlines and the like do harm.
+        if let Some(index) = self.inheritors_names.iter().position(|name| name.chars().any(char::is_control)) {
+            msg!("Inheritor {} has a name containing control characters", index);
+            return Err(WillError::NameHasControlCharacters.into());
+        }
+        // Released wills pay inheritors only, the owner must not be able to claim their own will.
+        if let Some(index) = self.inheritors_pubkeys.iter().position(|pubkey| pubkey == owner) {
+            msg!("Inheritor {} ({}) is the owner {}", index, self.inheritors_names[index], owner);
+            return Err(WillError::OwnerIsInheritor.into());
+        }
+        if let Some(index) = self.inheritors_shares.iter().position(|share| (*share).into() == 0) {
+            msg!("Inheritor {} ({}) has a share of zero", index, self.inheritors_names[index]);
+            return Err(WillError::ZeroShare.into());
+        }
+        let total_shares: u64 = self.inheritors_shares.iter().map(|share| u64::from((*share).into())).sum();
+        if total_shares > max_total_shares {
+            msg!("Shares add up to {}, more than the {} available", total_shares, max_total_shares);
+            return Err(WillError::ShareSumExceeded.into());
+        }
+        Ok(())
+    }
+}
+
+/// Builds `instruction` signed by `sender` for `will`, followed by `accounts`.
+fn will_instruction(program_id: &Pubkey, sender: &Pubkey, will: &Pubkey, instruction: &WillInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+    let mut metas = vec![AccountMeta::new(*sender, true), AccountMeta::new(*will, false)];
+    metas.extend(accounts);
+    Instruction::new_with_borsh(*program_id, instruction, metas)
+}
+
+/// Creates a `SetInheritance` instruction, shares in basis points.
+pub fn set_inheritance(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, message: SetInheritenceMessage) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetInheritance(message), vec![])
+}
+
+/// Creates a `SetInheritancePpm` instruction, shares in parts per million.
+pub fn set_inheritance_ppm(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, message: SetInheritenceMessage<u32>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetInheritancePpm(message), vec![])
+}
+
+/// Creates a `WithdrawSol` instruction.
+pub fn withdraw_sol(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, lamports: u64, refresh_timer: bool, dry_run: bool) -> Instruction {
+    let message = WithdrawSolMessage { lamports, refresh_timer, dry_run };
+    will_instruction(program_id, owner, will, &WillInstruction::WithdrawSol(message), vec![])
+}
+
+/// Creates a `Claim` instruction paying `destination`, or the sender with `None`.
+/// A delegate passes the inheritor they claim for as the destination.
+pub fn claim(program_id: &Pubkey, sender: &Pubkey, will: &Pubkey, destination: Option<Pubkey>, amount: Option<u64>, dry_run: bool) -> Instruction {
+    let accounts = destination.iter().map(|destination| AccountMeta::new(*destination, false)).collect();
+    will_instruction(program_id, sender, will, &WillInstruction::Claim(ClaimMessage { destination, amount, dry_run }), accounts)
+}
+
+/// Creates an `Initialize` instruction.
+pub fn initialize(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, timeout_seconds: i64) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::Initialize { timeout_seconds }, vec![])
+}
+
+/// Creates an `InitializeFrom` instruction copying the inheritors of `template`.
+pub fn initialize_from(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, template: &Pubkey, timeout_seconds: i64) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*template, false)];
+    will_instruction(program_id, owner, will, &WillInstruction::InitializeFrom { timeout_seconds }, accounts)
+}
+
+/// Creates a `Heartbeat` instruction.
+pub fn heartbeat(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::Heartbeat, vec![])
+}
+
+/// Creates a `ClaimWithTokens` instruction, paying out each pair of (will token
+/// account, inheritor token account).
+pub fn claim_with_tokens(program_id: &Pubkey, inheritor: &Pubkey, will: &Pubkey, token_accounts: &[(Pubkey, Pubkey)]) -> Instruction {
+    will_instruction(program_id, inheritor, will, &WillInstruction::ClaimWithTokens, token_account_metas(program_id, will, token_accounts))
+}
+
+/// The SPL Token program, the will's token authority and the (will token account,
+/// receiving token account) pairs taken by instructions moving the will's tokens.
+fn token_account_metas(program_id: &Pubkey, will: &Pubkey, token_accounts: &[(Pubkey, Pubkey)]) -> Vec<AccountMeta> {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(find_token_authority(will, program_id).0, false),
+    ];
+    for (source, destination) in token_accounts {
+        accounts.push(AccountMeta::new(*source, false));
+        accounts.push(AccountMeta::new(*destination, false));
+    }
+    accounts
+}
+
+/// Creates a `PreviewClaim` instruction.
+pub fn preview_claim(program_id: &Pubkey, inheritor: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, inheritor, will, &WillInstruction::PreviewClaim, vec![])
+}
+
+/// Creates a `Migrate` instruction.
+pub fn migrate(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::Migrate, vec![])
+}
+
+/// Creates a `Revoke` instruction, emptying `token_accounts` to the owner.
+pub fn revoke(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, token_accounts: &[(Pubkey, Pubkey)]) -> Instruction {
+    let accounts = if token_accounts.is_empty() { vec![] } else { token_account_metas(program_id, will, token_accounts) };
+    will_instruction(program_id, owner, will, &WillInstruction::Revoke, accounts)
+}
+
+/// Creates a `SetFrozen` instruction.
+pub fn set_frozen(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, frozen: bool) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetFrozen { frozen }, vec![])
+}
+
+/// Creates a `SetExecutor` instruction.
+pub fn set_executor(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, executor: Option<Pubkey>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetExecutor { executor }, vec![])
+}
+
+/// Creates an `Execute` instruction.
+pub fn execute(program_id: &Pubkey, executor: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, executor, will, &WillInstruction::Execute, vec![])
+}
+
+/// Creates a `ConfirmDeath` instruction.
+pub fn confirm_death(program_id: &Pubkey, guardian: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, guardian, will, &WillInstruction::ConfirmDeath, vec![])
+}
+
+/// Creates a `SetGuardians` instruction.
+pub fn set_guardians(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, guardians: Vec<Pubkey>, required_confirmations: u8) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetGuardians { guardians, required_confirmations }, vec![])
+}
+
+/// Creates a `Deposit` instruction.
+pub fn deposit(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, lamports: u64) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(system_program::id(), false)];
+    will_instruction(program_id, owner, will, &WillInstruction::Deposit { lamports }, accounts)
+}
+
+/// Creates an `UpdateInheritor` instruction.
+pub fn update_inheritor(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, index: u8, new_share: u32) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::UpdateInheritor { index, new_share }, vec![])
+}
+
+/// Creates an `AddInheritor` instruction.
+pub fn add_inheritor(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, name: String, pubkey: Pubkey, share: u32) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::AddInheritor { name, pubkey, share }, vec![])
+}
+
+/// Creates a `RemoveInheritor` instruction.
+pub fn remove_inheritor(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, index: u8) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::RemoveInheritor { index }, vec![])
+}
+
+/// Creates a `RenameInheritor` instruction.
+pub fn rename_inheritor(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, index: u8, new_name: String) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::RenameInheritor { index, new_name }, vec![])
+}
+
+/// Creates a `SetDelegate` instruction.
+pub fn set_delegate(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, index: u8, delegate: Option<Pubkey>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetDelegate { index, delegate }, vec![])
+}
+
+/// Creates a `SetContingent` instruction.
+pub fn set_contingent(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, index: u8, contingent_of: Option<u8>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetContingent { index, contingent_of }, vec![])
+}
+
+/// Creates a `VoteExtension` instruction.
+pub fn vote_extension(program_id: &Pubkey, inheritor: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, inheritor, will, &WillInstruction::VoteExtension, vec![])
+}
+
+/// Creates a `SetFallback` instruction.
+pub fn set_fallback(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, fallback: Option<Pubkey>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetFallback { fallback }, vec![])
+}
+
+/// Creates a `SweepToFallback` instruction, any `sender` may sweep.
+pub fn sweep_to_fallback(program_id: &Pubkey, sender: &Pubkey, will: &Pubkey, fallback: &Pubkey, token_accounts: &[(Pubkey, Pubkey)]) -> Instruction {
+    let mut accounts = vec![AccountMeta::new(*fallback, false)];
+    if !token_accounts.is_empty() {
+        accounts.extend(token_account_metas(program_id, will, token_accounts));
+    }
+    will_instruction(program_id, sender, will, &WillInstruction::SweepToFallback, accounts)
+}
+
+/// Creates a `TransferOwnership` instruction.
+pub fn transfer_ownership(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, new_owner: Pubkey) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::TransferOwnership { new_owner }, vec![])
+}
+
+/// Creates a `SetMinClaimInterval` instruction.
+pub fn set_min_claim_interval(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, seconds: i64) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetMinClaimInterval { seconds }, vec![])
+}
+
+/// Creates a `SetFixedAmount` instruction.
+pub fn set_fixed_amount(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, index: u8, fixed_amount: Option<(Pubkey, u64)>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetFixedAmount { index, fixed_amount }, vec![])
+}
+
+/// Creates a `Distribute` instruction paying `inheritors`.
+pub fn distribute(program_id: &Pubkey, executor: &Pubkey, will: &Pubkey, inheritors: &[Pubkey]) -> Instruction {
+    let accounts = inheritors.iter().map(|inheritor| AccountMeta::new(*inheritor, false)).collect();
+    will_instruction(program_id, executor, will, &WillInstruction::Distribute, accounts)
+}
+
+/// Creates a `SetWithdrawCap` instruction.
+pub fn set_withdraw_cap(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, max_withdraw_per_period: Option<u64>) -> Instruction {
+    will_instruction(program_id, owner, will, &WillInstruction::SetWithdrawCap { max_withdraw_per_period }, vec![])
+}
+
+/// Creates an `AddTokenAccount` instruction listing `token_account`.
+pub fn add_token_account(program_id: &Pubkey, owner: &Pubkey, will: &Pubkey, token_account: &Pubkey) -> Instruction {
+    let accounts = vec![AccountMeta::new_readonly(*token_account, false)];
+    will_instruction(program_id, owner, will, &WillInstruction::AddTokenAccount, accounts)
+}
+
+/// Creates a `QueryReleaseStatus` instruction.
+pub fn query_release_status(program_id: &Pubkey, sender: &Pubkey, will: &Pubkey) -> Instruction {
+    will_instruction(program_id, sender, will, &WillInstruction::QueryReleaseStatus, vec![])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unpack_set_inheritance() {
+        let heir = Pubkey::new_unique();
+        let data = (0_u8, vec![String::from("heir")], vec![heir], vec![10_000_u16], 600_i64, 60_i64).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::SetInheritance(SetInheritenceMessage {
+                inheritors_names: vec![String::from("heir")],
+                inheritors_pubkeys: vec![heir],
+                inheritors_shares: vec![10_000],
+                timeout_seconds: 600,
+                vesting_seconds: 60,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_unpack_set_inheritance_ppm() {
+        let heir = Pubkey::new_unique();
+        let data = (15_u8, vec![String::from("heir")], vec![heir], vec![333_333_u32], 600_i64, 0_i64).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::SetInheritancePpm(SetInheritenceMessage {
+                inheritors_names: vec![String::from("heir")],
+                inheritors_pubkeys: vec![heir],
+                inheritors_shares: vec![333_333],
+                timeout_seconds: 600,
+                vesting_seconds: 0,
+            }))
+        );
+    }
+
+    #[test]
+    fn test_unpack_instructions_with_fields() {
+        let data = (1_u8, 1_000_u64, false).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::WithdrawSol(WithdrawSolMessage { lamports: 1_000, refresh_timer: false, dry_run: false }))
+        );
+        let data = (1_u8, 1_000_u64).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::WithdrawSol(WithdrawSolMessage { lamports: 1_000, refresh_timer: true, dry_run: false }))
+        );
+        let data = (1_u8, 1_000_u64, false, true).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::WithdrawSol(WithdrawSolMessage { lamports: 1_000, refresh_timer: false, dry_run: true }))
+        );
+        let data = (14_u8, 1_000_u64).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::Deposit { lamports: 1_000 }));
+        let data = (3_u8, 600_i64).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::Initialize { timeout_seconds: 600 }));
+        assert_eq!(WillInstruction::unpack(&[9, 1]), Ok(WillInstruction::SetFrozen { frozen: true }));
+        assert_eq!(WillInstruction::unpack(&[9, 0]), Ok(WillInstruction::SetFrozen { frozen: false }));
+        let executor = Pubkey::new_unique();
+        let data = (10_u8, Some(executor)).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::SetExecutor { executor: Some(executor) }));
+        assert_eq!(WillInstruction::unpack(&[10, 0]), Ok(WillInstruction::SetExecutor { executor: None }));
+        let data = (16_u8, 2_u8, 1_500_u32).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::UpdateInheritor { index: 2, new_share: 1_500 }));
+        let data = (17_u8, String::from("heir"), executor, 1_500_u32).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::AddInheritor { name: String::from("heir"), pubkey: executor, share: 1_500 })
+        );
+        assert_eq!(WillInstruction::unpack(&[18, 3]), Ok(WillInstruction::RemoveInheritor { index: 3 }));
+        let data = (30_u8, 2_u8, String::from("heir")).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::RenameInheritor { index: 2, new_name: String::from("heir") }));
+        let data = (19_u8, 1_u8, Some(executor)).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::SetDelegate { index: 1, delegate: Some(executor) }));
+        assert_eq!(WillInstruction::unpack(&[19, 1, 0]), Ok(WillInstruction::SetDelegate { index: 1, delegate: None }));
+        assert_eq!(WillInstruction::unpack(&[31, 1, 1, 0]), Ok(WillInstruction::SetContingent { index: 1, contingent_of: Some(0) }));
+        assert_eq!(WillInstruction::unpack(&[31, 1, 0]), Ok(WillInstruction::SetContingent { index: 1, contingent_of: None }));
+        let data = (23_u8, executor).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::TransferOwnership { new_owner: executor }));
+        let data = (25_u8, 0_u8, Some((executor, 1_000_u64))).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::SetFixedAmount { index: 0, fixed_amount: Some((executor, 1_000)) }));
+        let data = (29_u8, 600_i64).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::InitializeFrom { timeout_seconds: 600 }));
+        let data = (28_u8, Some(1_000_u64)).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::SetWithdrawCap { max_withdraw_per_period: Some(1_000) }));
+        let data = (24_u8, 60_i64).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::SetMinClaimInterval { seconds: 60 }));
+        let data = (21_u8, Some(executor)).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::SetFallback { fallback: Some(executor) }));
+        let data = (13_u8, vec![executor], 1_u8).try_to_vec().unwrap();
+        assert_eq!(
+            WillInstruction::unpack(&data),
+            Ok(WillInstruction::SetGuardians { guardians: vec![executor], required_confirmations: 1 })
+        );
+    }
+
+    #[test]
+    fn test_unpack_claim() {
+        let destination = Pubkey::new_unique();
+        let data = (2_u8, Some(destination)).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::Claim(ClaimMessage { destination: Some(destination), amount: None, dry_run: false })));
+        assert_eq!(WillInstruction::unpack(&[2, 0]), Ok(WillInstruction::Claim(ClaimMessage { destination: None, amount: None, dry_run: false })));
+        assert_eq!(WillInstruction::unpack(&[2]), Ok(WillInstruction::Claim(ClaimMessage { destination: None, amount: None, dry_run: false })));
+        let data = (2_u8, None::<Pubkey>, Some(1_000_u64)).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Ok(WillInstruction::Claim(ClaimMessage { destination: None, amount: Some(1_000), dry_run: false })));
+        assert_eq!(WillInstruction::unpack(&[2, 0, 0]), Ok(WillInstruction::Claim(ClaimMessage { destination: None, amount: None, dry_run: false })));
+        assert_eq!(WillInstruction::unpack(&[2, 0, 0, 1]), Ok(WillInstruction::Claim(ClaimMessage { destination: None, amount: None, dry_run: true })));
+        assert_eq!(WillInstruction::unpack(&[2, 0, 1, 1]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(WillInstruction::unpack(&[2, 1, 2, 3]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(WillInstruction::unpack(&[2, 2]), Err(ProgramError::InvalidInstructionData));
+    }
+
+    #[test]
+    fn test_unpack_instructions_without_fields() {
+        let instructions = [
+            (4, WillInstruction::Heartbeat),
+            (5, WillInstruction::ClaimWithTokens),
+            (6, WillInstruction::PreviewClaim),
+            (7, WillInstruction::Migrate),
+            (8, WillInstruction::Revoke),
+            (11, WillInstruction::Execute),
+            (12, WillInstruction::ConfirmDeath),
+            (20, WillInstruction::VoteExtension),
+            (26, WillInstruction::QueryReleaseStatus),
+            (27, WillInstruction::Distribute),
+            (22, WillInstruction::SweepToFallback),
+            (32, WillInstruction::AddTokenAccount),
+        ];
+        for (selector, instruction) in instructions {
+            assert_eq!(WillInstruction::unpack(&[selector]).as_ref(), Ok(&instruction));
+            // Trailing bytes are ignored.
+            assert_eq!(WillInstruction::unpack(&[selector, 1, 2, 3]).as_ref(), Ok(&instruction));
+        }
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let heir = Pubkey::new_unique();
+        let instructions = [
+            WillInstruction::SetInheritance(SetInheritenceMessage {
+                inheritors_names: vec![String::from("heir")],
+                inheritors_pubkeys: vec![heir],
+                inheritors_shares: vec![10_000],
+                timeout_seconds: 600,
+                vesting_seconds: 60,
+            }),
+            WillInstruction::SetInheritancePpm(SetInheritenceMessage {
+                inheritors_names: vec![String::from("heir")],
+                inheritors_pubkeys: vec![heir],
+                inheritors_shares: vec![333_333],
+                timeout_seconds: 600,
+                vesting_seconds: 0,
+            }),
+            WillInstruction::WithdrawSol(WithdrawSolMessage { lamports: 1_000, refresh_timer: false, dry_run: false }),
+            WillInstruction::Claim(ClaimMessage { destination: Some(heir), amount: None, dry_run: false }),
+            WillInstruction::Claim(ClaimMessage { destination: None, amount: Some(1_000), dry_run: true }),
+            WillInstruction::SetGuardians { guardians: vec![heir], required_confirmations: 1 },
+            WillInstruction::Heartbeat,
+        ];
+        for instruction in instructions {
+            assert_eq!(WillInstruction::unpack(&instruction.try_to_vec().unwrap()), Ok(instruction));
+        }
+        let message = WithdrawSolMessage { lamports: 1_000, refresh_timer: true, dry_run: false };
+        assert_eq!(WithdrawSolMessage::try_from_slice(&message.try_to_vec().unwrap()).unwrap(), message);
+    }
+
+    #[test]
+    fn test_constructors_decode_back() {
+        let (program_id, sender, will, other) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let message = || SetInheritenceMessage {
+            inheritors_names: vec![String::from("heir")],
+            inheritors_pubkeys: vec![other],
+            inheritors_shares: vec![10_000],
+            timeout_seconds: 600,
+            vesting_seconds: 0,
+        };
+        let cases = vec![
+            (set_inheritance(&program_id, &sender, &will, message()), WillInstruction::SetInheritance(message())),
+            (withdraw_sol(&program_id, &sender, &will, 1_000, false, false), WillInstruction::WithdrawSol(WithdrawSolMessage { lamports: 1_000, refresh_timer: false, dry_run: false })),
+            (claim(&program_id, &sender, &will, None, None, false), WillInstruction::Claim(ClaimMessage { destination: None, amount: None, dry_run: false })),
+            (initialize(&program_id, &sender, &will, 600), WillInstruction::Initialize { timeout_seconds: 600 }),
+            (heartbeat(&program_id, &sender, &will), WillInstruction::Heartbeat),
+            (preview_claim(&program_id, &sender, &will), WillInstruction::PreviewClaim),
+            (migrate(&program_id, &sender, &will), WillInstruction::Migrate),
+            (revoke(&program_id, &sender, &will, &[]), WillInstruction::Revoke),
+            (set_frozen(&program_id, &sender, &will, true), WillInstruction::SetFrozen { frozen: true }),
+            (set_executor(&program_id, &sender, &will, Some(other)), WillInstruction::SetExecutor { executor: Some(other) }),
+            (execute(&program_id, &sender, &will), WillInstruction::Execute),
+            (confirm_death(&program_id, &sender, &will), WillInstruction::ConfirmDeath),
+            (
+                set_guardians(&program_id, &sender, &will, vec![other], 1),
+                WillInstruction::SetGuardians { guardians: vec![other], required_confirmations: 1 },
+            ),
+            (update_inheritor(&program_id, &sender, &will, 1, 500), WillInstruction::UpdateInheritor { index: 1, new_share: 500 }),
+            (
+                add_inheritor(&program_id, &sender, &will, String::from("heir"), other, 500),
+                WillInstruction::AddInheritor { name: String::from("heir"), pubkey: other, share: 500 },
+            ),
+            (remove_inheritor(&program_id, &sender, &will, 1), WillInstruction::RemoveInheritor { index: 1 }),
+            (
+                rename_inheritor(&program_id, &sender, &will, 1, String::from("heir")),
+                WillInstruction::RenameInheritor { index: 1, new_name: String::from("heir") },
+            ),
+            (set_delegate(&program_id, &sender, &will, 1, None), WillInstruction::SetDelegate { index: 1, delegate: None }),
+            (set_contingent(&program_id, &sender, &will, 1, Some(0)), WillInstruction::SetContingent { index: 1, contingent_of: Some(0) }),
+            (vote_extension(&program_id, &sender, &will), WillInstruction::VoteExtension),
+            (set_fallback(&program_id, &sender, &will, Some(other)), WillInstruction::SetFallback { fallback: Some(other) }),
+            (transfer_ownership(&program_id, &sender, &will, other), WillInstruction::TransferOwnership { new_owner: other }),
+            (set_min_claim_interval(&program_id, &sender, &will, 60), WillInstruction::SetMinClaimInterval { seconds: 60 }),
+            (set_fixed_amount(&program_id, &sender, &will, 1, Some((will, 1_000))), WillInstruction::SetFixedAmount { index: 1, fixed_amount: Some((will, 1_000)) }),
+            (query_release_status(&program_id, &sender, &will), WillInstruction::QueryReleaseStatus),
+            (set_withdraw_cap(&program_id, &sender, &will, None), WillInstruction::SetWithdrawCap { max_withdraw_per_period: None }),
+        ];
+        for (instruction, expected) in cases {
+            assert_eq!(instruction.program_id, program_id);
+            assert_eq!(instruction.accounts, vec![AccountMeta::new(sender, true), AccountMeta::new(will, false)]);
+            assert_eq!(WillInstruction::unpack(&instruction.data), Ok(expected));
+        }
+
+        let instruction = claim(&program_id, &sender, &will, Some(other), Some(1_000), false);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::Claim(ClaimMessage { destination: Some(other), amount: Some(1_000), dry_run: false })));
+        assert_eq!(instruction.accounts[2..], [AccountMeta::new(other, false)]);
+        let instruction = deposit(&program_id, &sender, &will, 1_000);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::Deposit { lamports: 1_000 }));
+        assert_eq!(instruction.accounts[2..], [AccountMeta::new_readonly(system_program::id(), false)]);
+        let instruction = sweep_to_fallback(&program_id, &sender, &will, &other, &[]);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::SweepToFallback));
+        assert_eq!(instruction.accounts[2..], [AccountMeta::new(other, false)]);
+        let instruction = sweep_to_fallback(&program_id, &sender, &will, &other, &[(will, other)]);
+        assert_eq!(instruction.accounts[3..5], [AccountMeta::new_readonly(spl_token::id(), false), AccountMeta::new_readonly(find_token_authority(&will, &program_id).0, false)]);
+        assert_eq!(instruction.accounts[5..], [AccountMeta::new(will, false), AccountMeta::new(other, false)]);
+        let instruction = initialize_from(&program_id, &sender, &will, &other, 600);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::InitializeFrom { timeout_seconds: 600 }));
+        assert_eq!(instruction.accounts[2..], [AccountMeta::new_readonly(other, false)]);
+        let instruction = add_token_account(&program_id, &sender, &will, &other);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::AddTokenAccount));
+        assert_eq!(instruction.accounts[2..], [AccountMeta::new_readonly(other, false)]);
+        let instruction = distribute(&program_id, &sender, &will, &[other, will]);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::Distribute));
+        assert_eq!(instruction.accounts[2..], [AccountMeta::new(other, false), AccountMeta::new(will, false)]);
+        let instruction = claim_with_tokens(&program_id, &sender, &will, &[(other, will)]);
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::ClaimWithTokens));
+        assert_eq!(
+            instruction.accounts[2..],
+            [
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(find_token_authority(&will, &program_id).0, false),
+                AccountMeta::new(other, false),
+                AccountMeta::new(will, false),
+            ]
+        );
+        let ppm = || SetInheritenceMessage {
+            inheritors_names: vec![String::from("heir")],
+            inheritors_pubkeys: vec![other],
+            inheritors_shares: vec![1_000_000],
+            timeout_seconds: 600,
+            vesting_seconds: 0,
+        };
+        let instruction = set_inheritance_ppm(&program_id, &sender, &will, ppm());
+        assert_eq!(WillInstruction::unpack(&instruction.data), Ok(WillInstruction::SetInheritancePpm(ppm())));
+    }
+
+    #[test]
+    fn test_unpack_malformed_data() {
+        assert_eq!(WillInstruction::unpack(&[]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(WillInstruction::unpack(&[99]), Err(ProgramError::InvalidInstructionData));
+        // Truncated fields.
+        assert_eq!(WillInstruction::unpack(&[1, 0, 0, 0]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(WillInstruction::unpack(&[3]), Err(ProgramError::InvalidInstructionData));
+        assert_eq!(WillInstruction::unpack(&[9, 2]), Err(ProgramError::InvalidInstructionData));
+        let data = (0_u8, vec![String::from("heir")], vec![Pubkey::new_unique()]).try_to_vec().unwrap();
+        assert_eq!(WillInstruction::unpack(&data), Err(ProgramError::InvalidInstructionData));
+    }
+}
@@ -1,252 +1,4045 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
+    account_info::AccountInfo,
     entrypoint,
     entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
-    pubkey::Pubkey,
+    pubkey::{Pubkey, PubkeyError},
     clock::{UnixTimestamp, Clock},
-    sysvar::Sysvar,
+    system_program,
+    sysvar::{self, Sysvar},
 };
+use std::{convert::TryFrom, str::FromStr};
 
+pub mod error;
+pub mod event;
+pub mod instruction;
+pub mod processor;
 
-#[derive(BorshSerialize, BorshDeserialize)]
+use crate::{error::WillError, processor::Processor};
+
+/// Layout version written by this program. Older layouts are upgraded when read.
+pub const SCHEMA_VERSION: u8 = 19;
+
+/// Shares are weights, each inheritor receives their share of the sum of all
+/// shares, so 1, 1 and 2 split an estate 25/25/50 just like 2500, 2500 and 5000
+/// basis points do. The sum is capped at this many, keeping it far from overflowing.
+pub const MAX_TOTAL_SHARES: u64 = 10_000;
+/// Shares set in parts per million, for estates split too finely for basis points.
+pub const MAX_TOTAL_SHARES_PPM: u64 = 1_000_000;
+
+/// Inheritors a will may list, keeping the account size and the cost of a claim bounded.
+pub const MAX_INHERITORS: usize = 32;
+
+/// Longest inheritor name in bytes of UTF-8, names are stored in the will.
+pub const MAX_NAME_BYTES: usize = 64;
+
+/// Guardians a will may list.
+pub const MAX_GUARDIANS: usize = 16;
+
+/// Token accounts a will may list, each one is passed to every completing claim.
+pub const MAX_TOKEN_ACCOUNTS: usize = 8;
+
+/// How long after its release a will must stay unclaimed before what is left
+/// may be swept to its fallback, see `WillInstruction::SweepToFallback`. A will
+/// vesting for longer is only swept once its shares fully vested.
+pub const FALLBACK_AFTER_SECONDS: i64 = 365 * 24 * 60 * 60;
+
+/// How far a quorum of inheritors may push the release back at once, see
+/// `WillInstruction::VoteExtension`.
+pub const GRACE_EXTENSION_SECONDS: i64 = 30 * 24 * 60 * 60;
+
+/// How often the inheritors may push the release back, see `WillData::extensions`.
+pub const MAX_EXTENSIONS: u8 = 3;
+
+/// Window `WillData::max_withdraw_per_period` caps withdrawals over, starting with
+/// the first withdrawal after the previous window ended.
+pub const WITHDRAW_PERIOD_SECONDS: i64 = 24 * 60 * 60;
+
+/// Longest `WillData::min_claim_interval` the owner may set, enough against spam and
+/// double submits without locking the owner out of their own will.
+pub const MAX_ACTION_INTERVAL_SECONDS: i64 = 24 * 60 * 60;
+
+/// How long after its release an inheritor with a contingent may leave their share
+/// unclaimed before it passes to the contingent, see `WillData::lapsed`.
+pub const CONTINGENCY_SECONDS: i64 = 90 * 24 * 60 * 60;
+
+/// Dead-man timeout of wills created before it became configurable.
+pub const DEFAULT_TIMEOUT_SECONDS: i64 = 5 * 60;
+/// Bounds on the dead-man timeout an owner may choose.
+pub const MIN_TIMEOUT_SECONDS: i64 = 5 * 60;
+pub const MAX_TIMEOUT_SECONDS: i64 = 100 * 365 * 24 * 60 * 60;
+
+/// Seed, together with the owner's key, of the address of every will this program
+/// has created so far, see `will_seed`.
+pub const WILL_SEED: &str = "solana-will.com/my/v3/1";
+
+/// Seed, together with the will address, of the will's token authority.
+pub const TOKEN_AUTHORITY_SEED: &[u8] = b"token-authority";
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
 pub struct InheritorInfo {
     pub name: String,
     pub pubkey: Pubkey,
-    pub share: u16,  // Divide by 10000.
+    pub share: u32,  // Weight, divide by the sum of all shares, which is at most `max_total_shares`.
+    pub claimed: u64,  // Lamports paid so far, claims only pay above it.
+    pub delegate: Option<Pubkey>,  // May claim on the inheritor's behalf, the inheritor is still paid.
+    pub fixed_amount: Option<(Pubkey, u64)>,  // Tokens of a mint paid before the rest the will holds of it is split by share.
+    pub contingent_of: Option<u8>,  // Index of the inheritor whose share this one takes if they lapse.
+    pub lapsed: bool,  // Recorded by the first claim after the share passed to the contingent.
 }
 
-#[derive(BorshSerialize, BorshDeserialize)]
+impl InheritorInfo {
+    /// Serialized size of an inheritor with an empty name: the name's length
+    /// prefix, pubkey, share, claimed lamports and room for a delegate, a fixed
+    /// amount with its mint, a contingency and its lapse. The name's bytes come on top.
+    pub const LEN: usize = 4 + 32 + 4 + 8 + 1 + 32 + 1 + 32 + 8 + 1 + 1 + 1;
+}
+
+/// Inheritors of schema versions 16 to 18 could not be contingent.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InheritorInfoV18 {
+    pub name: String,
+    pub pubkey: Pubkey,
+    pub share: u32,
+    pub claimed: u64,
+    pub delegate: Option<Pubkey>,
+    pub fixed_amount: Option<u64>,
+}
+
+impl From<InheritorInfoV18> for InheritorInfo {
+    fn from(old: InheritorInfoV18) -> Self {
+        InheritorInfo {
+            name: old.name,
+            pubkey: old.pubkey,
+            share: old.share,
+            claimed: old.claimed,
+            delegate: old.delegate,
+            // These named no mint and were paid out of every token account, the owner
+            // sets them again for the mint they meant.
+            fixed_amount: None,
+            contingent_of: None,
+            lapsed: false,
+        }
+    }
+}
+
+/// Inheritors of schema versions 11 to 15 had no fixed amount.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InheritorInfoV15 {
+    pub name: String,
+    pub pubkey: Pubkey,
+    pub share: u32,
+    pub claimed: u64,
+    pub delegate: Option<Pubkey>,
+}
+
+impl From<InheritorInfoV15> for InheritorInfoV18 {
+    fn from(old: InheritorInfoV15) -> Self {
+        InheritorInfoV18 { name: old.name, pubkey: old.pubkey, share: old.share, claimed: old.claimed, delegate: old.delegate, fixed_amount: None }
+    }
+}
+
+/// Inheritors of schema version 10 had no delegate.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InheritorInfoV10 {
+    pub name: String,
+    pub pubkey: Pubkey,
+    pub share: u32,
+    pub claimed: u64,
+}
+
+impl From<InheritorInfoV10> for InheritorInfoV15 {
+    fn from(old: InheritorInfoV10) -> Self {
+        InheritorInfoV15 { name: old.name, pubkey: old.pubkey, share: old.share, claimed: old.claimed, delegate: None }
+    }
+}
+
+/// Inheritors of schema versions 6 to 9 held basis point shares.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InheritorInfoV9 {
+    pub name: String,
+    pub pubkey: Pubkey,
+    pub share: u16,
+    pub claimed: u64,
+}
+
+impl From<InheritorInfoV9> for InheritorInfoV10 {
+    fn from(old: InheritorInfoV9) -> Self {
+        InheritorInfoV10 { name: old.name, pubkey: old.pubkey, share: old.share as u32, claimed: old.claimed }
+    }
+}
+
+#[derive(BorshSerialize, Debug)]
 pub struct WillData {
     pub schema_version: u8,  // Extendable, once you have version 255 on a first byte, next byte should be version as well.
     pub withdraw_allowed_ts: UnixTimestamp,
-    pub inheritors_names: Vec<String>,
-    pub inheritors_pubkeys: Vec<String>,
-    pub inheritors_shares: Vec<u16>,
+    pub inheritors: Vec<InheritorInfo>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,  // Default for wills created before the owner was stored.
+    pub vesting_seconds: i64,  // Claims vest linearly over this long after release, 0 pays out at once.
+    pub frozen: bool,  // Halts withdrawals and claims until the owner unfreezes the will, only set before the release.
+    pub executor: Option<Pubkey>,  // May release the will before the timeout, but never withdraw.
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,  // Guardians confirming the death release the will early, 0 never does.
+    pub confirmations: Vec<Pubkey>,  // Guardians who confirmed since the owner's last sign of life.
+    pub max_total_shares: u32,  // MAX_TOTAL_SHARES for basis points, MAX_TOTAL_SHARES_PPM for parts per million.
+    pub extension_votes: Vec<Pubkey>,  // Inheritors who voted to push the release back since the last extension.
+    pub last_heartbeat_ts: UnixTimestamp,  // Last sign of life of the owner, the release is `timeout_seconds` after it.
+    pub fallback: Option<Pubkey>,  // Receives what is left once the will stayed unclaimed `FALLBACK_AFTER_SECONDS` past its release.
+    pub min_claim_interval: i64,  // Owner instructions must come at least this many seconds apart, 0 never throttles.
+    pub last_action_ts: UnixTimestamp,  // When the last throttled instruction was processed.
+    pub distributable_snapshot: u64,  // Lamports to distribute, taken by the first claim after the release, 0 until then.
+    pub snapshot_total_shares: u64,  // Unclaimed shares when the snapshot was taken, what `distributable_snapshot` is split by.
+    pub max_withdraw_per_period: Option<u64>,  // Caps the lamports the owner withdraws within `WITHDRAW_PERIOD_SECONDS`, `None` never does.
+    pub last_withdraw_ts: UnixTimestamp,  // Start of the current withdrawal period, when the first withdrawal after the last period ended came.
+    pub withdrawn_in_period: u64,  // Lamports withdrawn since `last_withdraw_ts`.
+    pub pending_withdraw_cap: Option<(Option<u64>, UnixTimestamp)>,  // A raised or lifted cap and when it replaces `max_withdraw_per_period`.
+    pub extensions: u8,  // Times the inheritors pushed the release back since the last heartbeat.
+    pub token_accounts: Vec<Pubkey>,  // Held by the token authority, a share only completes with a claim paying out each, revoking or sweeping empties them.
     // pub coins_accounts: Vec<u16>,
     // pub coins_frozen_balances: Vec<u64>,
     // pub inherited_nfts: HashMap<Pubkey, Pubkey>,
     // pub frozen_balances: HashMap<Pubkey, u64>,
 }
 
-impl WillData {
-    fn check_released(&self) -> Result<(), ProgramError> {
-        let now = Clock::get()?.unix_timestamp;
-        if self.withdraw_allowed_ts < now {
-            return Ok(())
+/// Schema version 1 kept inheritor keys as base58 strings.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV1 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors_names: Vec<String>,
+    pub inheritors_pubkeys: Vec<String>,
+    pub inheritors_shares: Vec<u16>,
+}
+
+impl TryFrom<WillDataV1> for WillDataV2 {
+    type Error = ProgramError;
+
+    fn try_from(old: WillDataV1) -> Result<Self, Self::Error> {
+        let inheritors_pubkeys = old.inheritors_pubkeys.iter()
+            .map(|pubkey| Pubkey::from_str(pubkey).map_err(|_| {
+                msg!("Stored inheritor key {} is not a valid pubkey", pubkey);
+                ProgramError::InvalidAccountData
+            }))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(WillDataV2 {
+            schema_version: 2,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors_names: old.inheritors_names,
+            inheritors_pubkeys,
+            inheritors_shares: old.inheritors_shares,
+        })
+    }
+}
+
+/// Schema version 2 used the hardcoded `DEFAULT_TIMEOUT_SECONDS` for every will.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV2 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors_names: Vec<String>,
+    pub inheritors_pubkeys: Vec<Pubkey>,
+    pub inheritors_shares: Vec<u16>,
+}
+
+impl From<WillDataV2> for WillDataV3 {
+    fn from(old: WillDataV2) -> Self {
+        WillDataV3 {
+            schema_version: 3,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors_names: old.inheritors_names,
+            inheritors_pubkeys: old.inheritors_pubkeys,
+            inheritors_shares: old.inheritors_shares,
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
         }
-        msg!("Contract will be released at {}, but it is only {} now", self.withdraw_allowed_ts, now);
-        Err(ProgramError::Custom(1))
     }
-    fn get_share(&self, inheritor: &Pubkey) -> (u64, u64, usize) {
-        let mut total_shares = 0_u64;
-        let mut inheritor_shares = 0_u64;
-        let mut found_index = self.inheritors_shares.len();
-        let pubkeystr = inheritor.to_string();
-        for i in 0..self.inheritors_shares.len() as usize {
-            total_shares += self.inheritors_shares[i] as u64;
-            if self.inheritors_shares[i] > 0 && 
-                    self.inheritors_pubkeys[i] == pubkeystr &&
-                    found_index == self.inheritors_shares.len() {
-                inheritor_shares += self.inheritors_shares[i] as u64;
-                found_index = i;
-            }
+}
+
+/// Schema version 3 did not store the owner, only the will address was derived from it.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV3 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors_names: Vec<String>,
+    pub inheritors_pubkeys: Vec<Pubkey>,
+    pub inheritors_shares: Vec<u16>,
+    pub timeout_seconds: i64,
+}
+
+impl From<WillDataV3> for WillDataV4 {
+    fn from(old: WillDataV3) -> Self {
+        WillDataV4 {
+            schema_version: 4,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors_names: old.inheritors_names,
+            inheritors_pubkeys: old.inheritors_pubkeys,
+            inheritors_shares: old.inheritors_shares,
+            timeout_seconds: old.timeout_seconds,
+            owner: Pubkey::default(),
         }
-        (inheritor_shares, total_shares, found_index)
     }
 }
 
+/// Schema version 4 paid each inheritor out at once and did not record claims.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV4 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors_names: Vec<String>,
+    pub inheritors_pubkeys: Vec<Pubkey>,
+    pub inheritors_shares: Vec<u16>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+}
+
+impl From<WillDataV4> for WillDataV5 {
+    fn from(old: WillDataV4) -> Self {
+        WillDataV5 {
+            schema_version: 5,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors_claimed: vec![0; old.inheritors_shares.len()],
+            inheritors_names: old.inheritors_names,
+            inheritors_pubkeys: old.inheritors_pubkeys,
+            inheritors_shares: old.inheritors_shares,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: 0,
+        }
+    }
+}
 
-#[derive(BorshDeserialize)]
-pub struct SetInheritenceMessage {
-    pub selector: u8,
+/// Schema version 5 kept inheritors in parallel vectors that had to stay in lockstep.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV5 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
     pub inheritors_names: Vec<String>,
-    pub inheritors_pubkeys: Vec<String>,
+    pub inheritors_pubkeys: Vec<Pubkey>,
     pub inheritors_shares: Vec<u16>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub inheritors_claimed: Vec<u64>,
+    pub vesting_seconds: i64,
 }
 
-#[derive(BorshDeserialize)]
-pub struct WithdrawSolMessage {
-    pub selector: u8,
-    pub lamports: u64,
+impl TryFrom<WillDataV5> for WillDataV6 {
+    type Error = ProgramError;
+
+    fn try_from(old: WillDataV5) -> Result<Self, Self::Error> {
+        let lengths = [old.inheritors_names.len(), old.inheritors_pubkeys.len(), old.inheritors_shares.len(), old.inheritors_claimed.len()];
+        if lengths.iter().any(|len| *len != lengths[0]) {
+            msg!("Stored will has {} names, {} pubkeys, {} shares and {} claims, expected one of each per inheritor",
+                lengths[0], lengths[1], lengths[2], lengths[3]);
+            return Err(WillError::InheritorsLengthMismatch.into());
+        }
+        let inheritors = old.inheritors_names.into_iter()
+            .zip(old.inheritors_pubkeys)
+            .zip(old.inheritors_shares)
+            .zip(old.inheritors_claimed)
+            .map(|(((name, pubkey), share), claimed)| InheritorInfoV9 { name, pubkey, share, claimed })
+            .collect();
+        Ok(WillDataV6 {
+            schema_version: 6,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+        })
+    }
 }
 
-// Declare and export the program's entrypoint
-entrypoint!(process_instruction);
+/// Schema version 6 could not be frozen.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV6 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV9>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+}
 
-// Program entrypoint's implementation
-pub fn process_instruction(
-    program_id: &Pubkey, // Public key of the account the hello world program was loaded into
-    accounts: &[AccountInfo], // The account to say hello to
-    _instruction_data: &[u8],
-) -> ProgramResult {
-    msg!("Hello World Rust program entrypoint");
-
-    // Iterating accounts is safer then indexing
-    let accounts_iter = &mut accounts.iter();
-
-    // Get the account to say hello to
-    let sender = next_account_info(accounts_iter)?;
-    let account = next_account_info(accounts_iter)?;
-    
-    // The account must be owned by the program in order to modify its data
-    if account.owner != program_id {
-        msg!("Greeted account {} (owner = {}) does not have the correct program id {}", account.key, account.owner, program_id);
-        return Err(ProgramError::IncorrectProgramId);
+impl From<WillDataV6> for WillDataV7 {
+    fn from(old: WillDataV6) -> Self {
+        WillDataV7 {
+            schema_version: 7,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: false,
+        }
     }
+}
 
-    let timeout: i64 = 5 * 60;
-    match _instruction_data[0] {
-        // 0 -> Modify inheritors.
-        0 => {
-            check_ownership(account.key, sender.key, program_id)?;
-
-            let mut will_data = WillData::deserialize(&mut &account.data.borrow()[..])?;
-            let msg = SetInheritenceMessage::deserialize(&mut &_instruction_data[..])?;
-            will_data.schema_version = 1_u8;
-            will_data.withdraw_allowed_ts = Clock::get()?.unix_timestamp + timeout;
-            will_data.inheritors_names = msg.inheritors_names;
-            will_data.inheritors_pubkeys = msg.inheritors_pubkeys;
-            will_data.inheritors_shares = msg.inheritors_shares;
-            will_data.serialize(&mut &mut account.data.borrow_mut()[..])?;
-        },
-
-        // 1 - withdraw own funds SOL
-        1 => {
-            check_ownership(account.key, sender.key, program_id)?;
-
-            let msg = WithdrawSolMessage::deserialize(&mut &_instruction_data[..])?;
-            **account.try_borrow_mut_lamports()? -= msg.lamports;
-            **sender.try_borrow_mut_lamports()? += msg.lamports;
-
-            let mut will_data = WillData::deserialize(&mut &account.data.borrow()[..])?;
-            will_data.withdraw_allowed_ts = Clock::get()?.unix_timestamp + timeout;
-            will_data.serialize(&mut &mut account.data.borrow_mut()[..])?;
-        },
-
-        // 2 - withdraw inheritance
-        2 => {
-            let mut will_data = WillData::deserialize(&mut &account.data.borrow()[..])?;
-            will_data.check_released()?;
-
-            let (inheritor_shares, total_shares, inheritor_index) = will_data.get_share(sender.key);
-            if inheritor_shares == 0 {
-                return Err(ProgramError::Custom(2))
-            }
+/// Schema version 7 had no executor.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV7 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV9>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+}
 
-            let lamports_to_transfer = (**account.lamports.borrow()) / total_shares * inheritor_shares;
-            **account.try_borrow_mut_lamports()? -= lamports_to_transfer;
-            **sender.try_borrow_mut_lamports()? += lamports_to_transfer;
-            will_data.inheritors_shares[inheritor_index] = 0;
-            will_data.serialize(&mut &mut account.data.borrow_mut()[..])?;
-        },
-
-        3_u8..=u8::MAX => {}
-    }
-    //         if will_data.owner == Pubkey::new_from_array([0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]) {
-    //         // if account.data.borrow()[0] == 0 {
-    //             msg!("Initializing...");
-    //             will_data.owner = *sender.key;
-    //             will_data.withdraw_allowed_ts = Clock::get()?.unix_timestamp;
-    //             will_data.inheritor1 = Pubkey::new_from_array([0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]);
-    //             will_data.inheritor1_share = 33;
-    //             will_data.inheritor2 = Pubkey::new_from_array([0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]);
-    //             will_data.inheritor2_share = 33;
-    //             will_data.inheritor3 = Pubkey::new_from_array([0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]);
-    //             will_data.inheritor3_share = 34;
-    //             // will_data.owner = *sender.key;
-    //             will_data.serialize(&mut &mut account.data.borrow_mut()[..])?;
-    //         } else {
-    //             msg!("Trying to re-initialize account {}!", will_data.owner);
-    //             return Err(ProgramError::AccountAlreadyInitialized);
-    //         }
-    //     },
-    //     1 => {
-    //         if will_data.owner != *sender.key {
-    //             msg!("If you {} are not an owner {} you can not take back sol", *sender.key, will_data.owner);
-    //             return Err(ProgramError::InvalidAccountData);
-    //         }
-
-    //         **account.try_borrow_mut_lamports()? -= 1000000000;
-    //         **sender.try_borrow_mut_lamports()? += 1000000000;
-    //     },
-    //     2_u8..=u8::MAX => {}
-    // }
-    //
-    // Increment and store the number of times the account has been greeted
-    //
-    // greeting_account.counter += 1;
-    // greeting_account.serialize(&mut &mut account.data.borrow_mut()[..])?;
-    
-    //msg!("Greeted {} time(s)!", greeting_account.counter);
-
-// }
-    // account.data.borrow_mut()[0] = 0x1;
+impl From<WillDataV7> for WillDataV8 {
+    fn from(old: WillDataV7) -> Self {
+        WillDataV8 {
+            schema_version: 8,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: None,
+        }
+    }
+}
 
-    Ok(())
+/// Schema version 8 had no guardians.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV8 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV9>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
 }
 
-fn check_ownership(account_key: &Pubkey, sender_key: &Pubkey, program_id: &Pubkey) -> Result<(), ProgramError> {
-    let seed = "solana-will.com/my/v3/1";
-    let expected_account = Pubkey::create_with_seed(sender_key, seed, program_id)?;
-    if *account_key != expected_account {
-        // msg!("Sender {} with seed {} should be {} But got {}", sender_key, seed, expected_account, account_key);
-        msg!("Sender {} with seed {} should be {}", sender_key, seed, expected_account);
-        msg!("But got {}", account_key);
-        return Err(ProgramError::IncorrectProgramId);
+impl From<WillDataV8> for WillDataV9 {
+    fn from(old: WillDataV8) -> Self {
+        WillDataV9 {
+            schema_version: 9,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+        }
     }
-    Ok(())
 }
 
-// Sanity tests
-#[cfg(test)]
-mod test {
-    use super::*;
-    use solana_program::clock::Epoch;
-    use std::mem;
+/// Schema version 9 counted every share in basis points.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV9 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV9>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+}
 
-    #[test]
-    fn test_sanity() {
-        let program_id = Pubkey::default();
-        let key = Pubkey::default();
-        let mut lamports = 0;
-        let mut data = vec![0; mem::size_of::<u32>()];
-        let owner = Pubkey::default();
-        let account = AccountInfo::new(
-            &key,
-            false,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner,
-            false,
-            Epoch::default(),
-        );
-        let instruction_data: Vec<u8> = Vec::new();
+impl From<WillDataV9> for WillDataV10 {
+    fn from(old: WillDataV9) -> Self {
+        WillDataV10 {
+            schema_version: 10,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors.into_iter().map(InheritorInfoV10::from).collect(),
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+        }
+    }
+}
 
-        let accounts = vec![account];
+/// Schema version 10 had no delegates.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV10 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV10>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+}
 
-        assert_eq!(
-            WillData::try_from_slice(&accounts[0].data.borrow())
-                .unwrap()
-                .counter,
-            0
-        );
-        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
-        assert_eq!(
-            WillData::try_from_slice(&accounts[0].data.borrow())
-                .unwrap()
-                .counter,
-            1
-        );
-        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
-        assert_eq!(
-            WillData::try_from_slice(&accounts[0].data.borrow())
-                .unwrap()
-                .counter,
-            2
+impl From<WillDataV10> for WillDataV11 {
+    fn from(old: WillDataV10) -> Self {
+        WillDataV11 {
+            schema_version: 11,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors.into_iter().map(InheritorInfoV15::from).collect(),
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+        }
+    }
+}
+
+/// Schema version 11 had no extension votes.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV11 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV15>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+}
+
+impl From<WillDataV11> for WillDataV12 {
+    fn from(old: WillDataV11) -> Self {
+        WillDataV12 {
+            schema_version: 12,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: vec![],
+        }
+    }
+}
+
+/// Schema version 12 did not record the last heartbeat.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV12 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV15>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+}
+
+impl From<WillDataV12> for WillDataV13 {
+    fn from(old: WillDataV12) -> Self {
+        WillDataV13 {
+            schema_version: 13,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.withdraw_allowed_ts.saturating_sub(old.timeout_seconds),
+        }
+    }
+}
+
+/// Schema version 13 had no fallback.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV13 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV15>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+    pub last_heartbeat_ts: UnixTimestamp,
+}
+
+impl From<WillDataV13> for WillDataV14 {
+    fn from(old: WillDataV13) -> Self {
+        WillDataV14 {
+            schema_version: 14,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.last_heartbeat_ts,
+            fallback: None,
+        }
+    }
+}
+
+/// Schema version 14 did not throttle instructions.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV14 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV15>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+    pub last_heartbeat_ts: UnixTimestamp,
+    pub fallback: Option<Pubkey>,
+}
+
+impl From<WillDataV14> for WillDataV15 {
+    fn from(old: WillDataV14) -> Self {
+        WillDataV15 {
+            schema_version: 15,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.last_heartbeat_ts,
+            fallback: old.fallback,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+        }
+    }
+}
+
+/// Schema version 15 had no fixed amounts.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV15 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV15>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+    pub last_heartbeat_ts: UnixTimestamp,
+    pub fallback: Option<Pubkey>,
+    pub min_claim_interval: i64,
+    pub last_action_ts: UnixTimestamp,
+}
+
+impl From<WillDataV15> for WillDataV16 {
+    fn from(old: WillDataV15) -> Self {
+        WillDataV16 {
+            schema_version: 16,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors.into_iter().map(InheritorInfoV18::from).collect(),
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.last_heartbeat_ts,
+            fallback: old.fallback,
+            min_claim_interval: old.min_claim_interval,
+            last_action_ts: old.last_action_ts,
+        }
+    }
+}
+
+/// Schema version 16 split the current balance on every claim.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV16 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV18>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+    pub last_heartbeat_ts: UnixTimestamp,
+    pub fallback: Option<Pubkey>,
+    pub min_claim_interval: i64,
+    pub last_action_ts: UnixTimestamp,
+}
+
+impl From<WillDataV16> for WillDataV17 {
+    fn from(old: WillDataV16) -> Self {
+        WillDataV17 {
+            schema_version: 17,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.last_heartbeat_ts,
+            fallback: old.fallback,
+            min_claim_interval: old.min_claim_interval,
+            last_action_ts: old.last_action_ts,
+            distributable_snapshot: 0,
+            snapshot_total_shares: 0,
+        }
+    }
+}
+
+/// Schema version 17 did not cap withdrawals.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV17 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV18>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+    pub last_heartbeat_ts: UnixTimestamp,
+    pub fallback: Option<Pubkey>,
+    pub min_claim_interval: i64,
+    pub last_action_ts: UnixTimestamp,
+    pub distributable_snapshot: u64,
+    pub snapshot_total_shares: u64,
+}
+
+impl From<WillDataV17> for WillDataV18 {
+    fn from(old: WillDataV17) -> Self {
+        WillDataV18 {
+            schema_version: 18,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors,
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.last_heartbeat_ts,
+            fallback: old.fallback,
+            min_claim_interval: old.min_claim_interval,
+            last_action_ts: old.last_action_ts,
+            distributable_snapshot: old.distributable_snapshot,
+            snapshot_total_shares: old.snapshot_total_shares,
+            max_withdraw_per_period: None,
+            last_withdraw_ts: 0,
+            withdrawn_in_period: 0,
+        }
+    }
+}
+
+/// Schema version 18 had no contingent inheritors.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct WillDataV18 {
+    pub schema_version: u8,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub inheritors: Vec<InheritorInfoV18>,
+    pub timeout_seconds: i64,
+    pub owner: Pubkey,
+    pub vesting_seconds: i64,
+    pub frozen: bool,
+    pub executor: Option<Pubkey>,
+    pub guardians: Vec<Pubkey>,
+    pub required_confirmations: u8,
+    pub confirmations: Vec<Pubkey>,
+    pub max_total_shares: u32,
+    pub extension_votes: Vec<Pubkey>,
+    pub last_heartbeat_ts: UnixTimestamp,
+    pub fallback: Option<Pubkey>,
+    pub min_claim_interval: i64,
+    pub last_action_ts: UnixTimestamp,
+    pub distributable_snapshot: u64,
+    pub snapshot_total_shares: u64,
+    pub max_withdraw_per_period: Option<u64>,
+    pub last_withdraw_ts: UnixTimestamp,
+    pub withdrawn_in_period: u64,
+}
+
+impl From<WillDataV18> for WillData {
+    fn from(old: WillDataV18) -> Self {
+        WillData {
+            schema_version: SCHEMA_VERSION,
+            withdraw_allowed_ts: old.withdraw_allowed_ts,
+            inheritors: old.inheritors.into_iter().map(InheritorInfo::from).collect(),
+            timeout_seconds: old.timeout_seconds,
+            owner: old.owner,
+            vesting_seconds: old.vesting_seconds,
+            frozen: old.frozen,
+            executor: old.executor,
+            guardians: old.guardians,
+            required_confirmations: old.required_confirmations,
+            confirmations: old.confirmations,
+            max_total_shares: old.max_total_shares,
+            extension_votes: old.extension_votes,
+            last_heartbeat_ts: old.last_heartbeat_ts,
+            fallback: old.fallback,
+            min_claim_interval: old.min_claim_interval,
+            last_action_ts: old.last_action_ts,
+            distributable_snapshot: old.distributable_snapshot,
+            snapshot_total_shares: old.snapshot_total_shares,
+            max_withdraw_per_period: old.max_withdraw_per_period,
+            last_withdraw_ts: old.last_withdraw_ts,
+            withdrawn_in_period: old.withdrawn_in_period,
+            pending_withdraw_cap: None,
+            extensions: 0,
+            token_accounts: vec![],
+        }
+    }
+}
+
+/// A will as read in one of the layouts `WillData::read` understands. Supporting
+/// a new schema version means adding its predecessor here, with one upgrade step.
+enum Layout {
+    V1(WillDataV1),
+    V2(WillDataV2),
+    V3(WillDataV3),
+    V4(WillDataV4),
+    V5(WillDataV5),
+    V6(WillDataV6),
+    V7(WillDataV7),
+    V8(WillDataV8),
+    V9(WillDataV9),
+    V10(WillDataV10),
+    V11(WillDataV11),
+    V12(WillDataV12),
+    V13(WillDataV13),
+    V14(WillDataV14),
+    V15(WillDataV15),
+    V16(WillDataV16),
+    V17(WillDataV17),
+    V18(WillDataV18),
+    Current(WillData),
+}
+
+impl Layout {
+    /// Converts the will to the layout of the next schema version.
+    fn upgrade(self) -> Result<Self, ProgramError> {
+        Ok(match self {
+            Layout::V1(old) => Layout::V2(WillDataV2::try_from(old)?),
+            Layout::V2(old) => Layout::V3(old.into()),
+            Layout::V3(old) => Layout::V4(old.into()),
+            Layout::V4(old) => Layout::V5(old.into()),
+            Layout::V5(old) => Layout::V6(WillDataV6::try_from(old)?),
+            Layout::V6(old) => Layout::V7(old.into()),
+            Layout::V7(old) => Layout::V8(old.into()),
+            Layout::V8(old) => Layout::V9(old.into()),
+            Layout::V9(old) => Layout::V10(old.into()),
+            Layout::V10(old) => Layout::V11(old.into()),
+            Layout::V11(old) => Layout::V12(old.into()),
+            Layout::V12(old) => Layout::V13(old.into()),
+            Layout::V13(old) => Layout::V14(old.into()),
+            Layout::V14(old) => Layout::V15(old.into()),
+            Layout::V15(old) => Layout::V16(old.into()),
+            Layout::V16(old) => Layout::V17(old.into()),
+            Layout::V17(old) => Layout::V18(old.into()),
+            Layout::V18(old) => Layout::Current(old.into()),
+            Layout::Current(will_data) => Layout::Current(will_data),
+        })
+    }
+}
+
+/// Reads wills of every layout, see `WillData::unpack`, which also validates them.
+impl BorshDeserialize for WillData {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        Self::read(buf).map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+    }
+}
+
+impl WillData {
+    /// Serialized size of a will without inheritors, guardians, confirmations,
+    /// extension votes or token accounts, with room for an executor and a fallback.
+    pub const LEN: usize = 1  // schema_version
+        + 8  // withdraw_allowed_ts
+        + 4  // inheritors length prefix
+        + 8  // timeout_seconds
+        + 32  // owner
+        + 8  // vesting_seconds
+        + 1  // frozen
+        + 1 + 32  // executor
+        + 4  // guardians length prefix
+        + 1  // required_confirmations
+        + 4  // confirmations length prefix
+        + 4  // max_total_shares
+        + 4  // extension_votes length prefix
+        + 8  // last_heartbeat_ts
+        + 1 + 32  // fallback
+        + 8  // min_claim_interval
+        + 8  // last_action_ts
+        + 8  // distributable_snapshot
+        + 8  // snapshot_total_shares
+        + 1 + 8  // max_withdraw_per_period
+        + 8  // last_withdraw_ts
+        + 8  // withdrawn_in_period
+        + 1 + 1 + 8 + 8  // pending_withdraw_cap
+        + 1  // extensions
+        + 4;  // token_accounts length prefix
+
+    /// Bytes a will listing `n_inheritors` needs, to pass to `create_account_with_seed`.
+    /// Inheritor names add their UTF-8 bytes on top, at most `MAX_NAME_BYTES` each,
+    /// every guardian, confirmation, extension vote and token account 32 bytes.
+    pub fn size_for(n_inheritors: usize) -> usize {
+        Self::LEN + n_inheritors * InheritorInfo::LEN
+    }
+
+    /// Reads a will in any layout up to `SCHEMA_VERSION` off the front of `buf`,
+    /// by the schema version in its first byte. Fields older layouts lack get
+    /// their defaults.
+    fn read(buf: &mut &[u8]) -> Result<Self, ProgramError> {
+        let mut layout = match buf.first() {
+            Some(0) | Some(1) => Layout::V1(WillDataV1::deserialize(buf)?),
+            Some(2) => Layout::V2(WillDataV2::deserialize(buf)?),
+            Some(3) => Layout::V3(WillDataV3::deserialize(buf)?),
+            Some(4) => Layout::V4(WillDataV4::deserialize(buf)?),
+            Some(5) => Layout::V5(WillDataV5::deserialize(buf)?),
+            Some(6) => Layout::V6(WillDataV6::deserialize(buf)?),
+            Some(7) => Layout::V7(WillDataV7::deserialize(buf)?),
+            Some(8) => Layout::V8(WillDataV8::deserialize(buf)?),
+            Some(9) => Layout::V9(WillDataV9::deserialize(buf)?),
+            Some(10) => Layout::V10(WillDataV10::deserialize(buf)?),
+            Some(11) => Layout::V11(WillDataV11::deserialize(buf)?),
+            Some(12) => Layout::V12(WillDataV12::deserialize(buf)?),
+            Some(13) => Layout::V13(WillDataV13::deserialize(buf)?),
+            Some(14) => Layout::V14(WillDataV14::deserialize(buf)?),
+            Some(15) => Layout::V15(WillDataV15::deserialize(buf)?),
+            Some(16) => Layout::V16(WillDataV16::deserialize(buf)?),
+            Some(17) => Layout::V17(WillDataV17::deserialize(buf)?),
+            Some(18) => Layout::V18(WillDataV18::deserialize(buf)?),
+            // Fields are read in the order written, which must be the order they are declared in.
+            Some(&SCHEMA_VERSION) => Layout::Current(WillData {
+                schema_version: BorshDeserialize::deserialize(buf)?,
+                withdraw_allowed_ts: BorshDeserialize::deserialize(buf)?,
+                inheritors: BorshDeserialize::deserialize(buf)?,
+                timeout_seconds: BorshDeserialize::deserialize(buf)?,
+                owner: BorshDeserialize::deserialize(buf)?,
+                vesting_seconds: BorshDeserialize::deserialize(buf)?,
+                frozen: BorshDeserialize::deserialize(buf)?,
+                executor: BorshDeserialize::deserialize(buf)?,
+                guardians: BorshDeserialize::deserialize(buf)?,
+                required_confirmations: BorshDeserialize::deserialize(buf)?,
+                confirmations: BorshDeserialize::deserialize(buf)?,
+                max_total_shares: BorshDeserialize::deserialize(buf)?,
+                extension_votes: BorshDeserialize::deserialize(buf)?,
+                last_heartbeat_ts: BorshDeserialize::deserialize(buf)?,
+                fallback: BorshDeserialize::deserialize(buf)?,
+                min_claim_interval: BorshDeserialize::deserialize(buf)?,
+                last_action_ts: BorshDeserialize::deserialize(buf)?,
+                distributable_snapshot: BorshDeserialize::deserialize(buf)?,
+                snapshot_total_shares: BorshDeserialize::deserialize(buf)?,
+                max_withdraw_per_period: BorshDeserialize::deserialize(buf)?,
+                last_withdraw_ts: BorshDeserialize::deserialize(buf)?,
+                withdrawn_in_period: BorshDeserialize::deserialize(buf)?,
+                pending_withdraw_cap: BorshDeserialize::deserialize(buf)?,
+                extensions: BorshDeserialize::deserialize(buf)?,
+                token_accounts: BorshDeserialize::deserialize(buf)?,
+            }),
+            Some(newer) => {
+                msg!("Will schema version {} is newer than {}, upgrade the program", newer, SCHEMA_VERSION);
+                return Err(WillError::UnsupportedSchemaVersion.into());
+            }
+            None => {
+                msg!("Will account holds no data");
+                return Err(ProgramError::InvalidAccountData);
+            }
+        };
+        loop {
+            match layout {
+                Layout::Current(will_data) => return Ok(will_data),
+                older => layout = older.upgrade()?,
+            }
+        }
+    }
+
+    /// Reads the will from account data. Accounts still in an older layout
+    /// (including freshly zeroed ones) are converted to `SCHEMA_VERSION`, and
+    /// are stored that way the next time the will is written. Wills breaking the
+    /// rules of `validate` are rejected rather than acted on.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let will_data = Self::read(&mut &*data)?;
+        will_data.validate()?;
+        Ok(will_data)
+    }
+
+    /// Checks the limits every instruction writing the will upholds, so a corrupt
+    /// will fails with the error of the broken limit instead of somewhere deep
+    /// inside an instruction.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.inheritors.len() > MAX_INHERITORS {
+            msg!("Stored will lists {} inheritors, at most {} are allowed", self.inheritors.len(), MAX_INHERITORS);
+            return Err(WillError::TooManyInheritors.into());
+        }
+        let total_shares = self.total_shares();
+        if total_shares > self.max_total_shares as u64 {
+            msg!("Stored shares add up to {}, more than the {} available", total_shares, self.max_total_shares);
+            return Err(WillError::ShareSumExceeded.into());
+        }
+        if self.guardians.len() > MAX_GUARDIANS {
+            msg!("Stored will lists {} guardians, at most {} are allowed", self.guardians.len(), MAX_GUARDIANS);
+            return Err(WillError::TooManyGuardians.into());
+        }
+        if self.token_accounts.len() > MAX_TOKEN_ACCOUNTS {
+            msg!("Stored will lists {} token accounts, at most {} are allowed", self.token_accounts.len(), MAX_TOKEN_ACCOUNTS);
+            return Err(WillError::TooManyTokenAccounts.into());
+        }
+        if self.required_confirmations as usize > self.guardians.len() || self.confirmations.len() > self.guardians.len() {
+            msg!("Stored will requires {} and holds {} confirmations of {} guardians",
+                self.required_confirmations, self.confirmations.len(), self.guardians.len());
+            return Err(WillError::ConfirmationsOutOfBounds.into());
+        }
+        self.check_contingents()
+    }
+
+    /// Checks that every contingent names another inheritor of the will, that no
+    /// inheritor has more than one contingent, and that contingents have none, so a
+    /// lapsed share passes on exactly once.
+    pub fn check_contingents(&self) -> Result<(), ProgramError> {
+        for (index, info) in self.inheritors.iter().enumerate() {
+            if let Some(primary) = info.contingent_of {
+                let primary = primary as usize;
+                if primary == index || primary >= self.inheritors.len() {
+                    msg!("Inheritor {} is contingent of inheritor {}, the will lists {}", index, primary, self.inheritors.len());
+                    return Err(WillError::InvalidContingent.into());
+                }
+                if self.contingent(primary) != Some(index) {
+                    msg!("Inheritor {} has more than one contingent", primary);
+                    return Err(WillError::InvalidContingent.into());
+                }
+                if self.inheritors[primary].contingent_of.is_some() {
+                    msg!("Inheritor {} is a contingent and can not have one", primary);
+                    return Err(WillError::InvalidContingent.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the will to account data. Fails with `AccountDataTooSmall`, rather
+    /// than storing a truncated will, if the account is too small to hold it.
+    /// The rest of the account is zeroed, so bytes left over from a larger will
+    /// can never be read back as part of a later layout.
+    pub fn pack(&self, data: &mut [u8]) -> ProgramResult {
+        let will_data = self.try_to_vec()?;
+        if will_data.len() > data.len() {
+            msg!("Will needs {} bytes, but the account only has {}", will_data.len(), data.len());
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        let (will, tail) = data.split_at_mut(will_data.len());
+        will.copy_from_slice(&will_data);
+        tail.fill(0);
+        Ok(())
+    }
+
+    /// Inheritors may claim from `withdraw_allowed_ts` on, the release second included.
+    pub fn is_released(&self) -> Result<bool, ProgramError> {
+        Ok(self.withdraw_allowed_ts <= Clock::get()?.unix_timestamp)
+    }
+
+    /// Fails with `NotYetReleased` unless the will `is_released`.
+    fn check_released(&self) -> Result<(), ProgramError> {
+        if self.is_released()? {
+            return Ok(())
+        }
+        msg!("Contract will be released at {}, but it is only {} now", self.withdraw_allowed_ts, Clock::get()?.unix_timestamp);
+        Err(WillError::NotYetReleased.into())
+    }
+
+    /// Whether the will is released at `now`, see `check_released`, and how long is left until it is.
+    pub fn release_status(&self, now: UnixTimestamp) -> ReleaseStatus {
+        ReleaseStatus {
+            released: self.withdraw_allowed_ts <= now,
+            withdraw_allowed_ts: self.withdraw_allowed_ts,
+            seconds_remaining: self.withdraw_allowed_ts.saturating_sub(now).max(0),
+        }
+    }
+
+    /// Only the owner may act on the will as its owner. The stored owner decides, so
+    /// the will keeps working after `TransferOwnership` although its address was
+    /// derived from the first owner's key. Wills from before the owner was stored
+    /// are adopted by the sender, whose key the will address must be derived from.
+    fn check_owner(&mut self, account: &AccountInfo, sender: &Pubkey, program_id: &Pubkey) -> Result<(), ProgramError> {
+        if self.owner == Pubkey::default() {
+            check_ownership(account, sender, program_id)?;
+            self.owner = *sender;
+        }
+        if self.owner != *sender {
+            msg!("Sender {} is not the owner {} of the will", sender, self.owner);
+            return Err(WillError::NotTheOwner.into());
+        }
+        Ok(())
+    }
+
+    /// The owner showed a sign of life, the dead-man timer restarts from now.
+    fn record_heartbeat(&mut self) -> ProgramResult {
+        self.set_withdraw_allowed_ts(release_time(self.timeout_seconds)?);
+        self.last_heartbeat_ts = Clock::get()?.unix_timestamp;
+        self.distributable_snapshot = 0;
+        self.snapshot_total_shares = 0;
+        self.extensions = 0;
+        Ok(())
+    }
+
+    /// Moves the release to `withdraw_allowed_ts`, logging the change for debugging
+    /// the dead-man timer.
+    fn set_withdraw_allowed_ts(&mut self, withdraw_allowed_ts: UnixTimestamp) {
+        if withdraw_allowed_ts != self.withdraw_allowed_ts {
+            msg!("timer {} -> {}", self.withdraw_allowed_ts, withdraw_allowed_ts);
+        }
+        self.withdraw_allowed_ts = withdraw_allowed_ts;
+    }
+
+    /// Lowers the withdrawal cap at once, while raising or lifting it only takes effect
+    /// `WITHDRAW_PERIOD_SECONDS` after `now`, so a stolen owner key can not lift the cap
+    /// and drain the will in one go. Returns when the cap takes effect.
+    fn set_withdraw_cap(&mut self, max_withdraw_per_period: Option<u64>, now: UnixTimestamp) -> UnixTimestamp {
+        let lowers = match (max_withdraw_per_period, self.max_withdraw_per_period) {
+            (Some(cap), Some(current)) => cap <= current,
+            (Some(_), None) => true,
+            (None, current) => current.is_none(),
+        };
+        if lowers {
+            // Also drops a pending raise, which the new cap replaces.
+            self.max_withdraw_per_period = max_withdraw_per_period;
+            self.pending_withdraw_cap = None;
+            return now;
+        }
+        let effective_ts = now.saturating_add(WITHDRAW_PERIOD_SECONDS);
+        self.pending_withdraw_cap = Some((max_withdraw_per_period, effective_ts));
+        effective_ts
+    }
+
+    /// Counts `lamports` withdrawn by the owner against `max_withdraw_per_period`,
+    /// starting a new period if the last one is over.
+    fn record_withdrawal(&mut self, lamports: u64) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+        if let Some((max_withdraw_per_period, effective_ts)) = self.pending_withdraw_cap {
+            if now >= effective_ts {
+                self.max_withdraw_per_period = max_withdraw_per_period;
+                self.pending_withdraw_cap = None;
+            }
+        }
+        if self.withdrawn_in_period == 0 || now >= self.last_withdraw_ts.saturating_add(WITHDRAW_PERIOD_SECONDS) {
+            self.last_withdraw_ts = now;
+            self.withdrawn_in_period = 0;
+        }
+        let withdrawn = self.withdrawn_in_period.saturating_add(lamports);
+        if let Some(max_withdraw_per_period) = self.max_withdraw_per_period {
+            if withdrawn > max_withdraw_per_period {
+                msg!("Withdrawing {} lamports on top of {} since {} exceeds the cap of {}",
+                    lamports, self.withdrawn_in_period, self.last_withdraw_ts, max_withdraw_per_period);
+                return Err(WillError::WithdrawCapExceeded.into());
+            }
+        }
+        self.withdrawn_in_period = withdrawn;
+        Ok(())
+    }
+
+    /// Owner instructions must come `min_claim_interval` apart, this one is recorded
+    /// as the last if it does. Claims are not throttled, one inheritor claiming must
+    /// not hold up the others.
+    fn record_action(&mut self) -> ProgramResult {
+        let now = Clock::get()?.unix_timestamp;
+        let next_action_ts = self.last_action_ts.saturating_add(self.min_claim_interval);
+        if self.min_claim_interval > 0 && now < next_action_ts {
+            msg!("Last action on the will was at {}, the next may come at {}", self.last_action_ts, next_action_ts);
+            return Err(WillError::ActionTooSoon.into());
+        }
+        self.last_action_ts = now;
+        Ok(())
+    }
+
+    /// Only a delegate the owner appointed for `inheritor` may claim on their behalf.
+    fn check_delegate(&self, inheritor: &Pubkey, delegate: &Pubkey) -> Result<(), ProgramError> {
+        if self.inheritors.iter().any(|info| info.pubkey == *inheritor && info.delegate == Some(*delegate)) {
+            return Ok(())
+        }
+        msg!("Sender {} is not a delegate of inheritor {}", delegate, inheritor);
+        Err(WillError::NotTheDelegate.into())
+    }
+
+    /// Nothing leaves a frozen will, neither the owner's withdrawals nor inheritance.
+    fn check_not_frozen(&self) -> Result<(), ProgramError> {
+        if !self.frozen {
+            return Ok(())
+        }
+        msg!("Will is frozen, the owner must unfreeze it first");
+        Err(WillError::Frozen.into())
+    }
+
+    /// Reads the will for an instruction only its owner may sign, which counts as an
+    /// action and is refused once the will is released.
+    fn unpack_for_owner(account: &AccountInfo, sender: &AccountInfo, program_id: &Pubkey) -> Result<Self, ProgramError> {
+        check_signer(sender)?;
+        let mut will_data = Self::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+        will_data.check_not_released()?;
+        Ok(will_data)
+    }
+
+    /// Checks that the will lists an inheritor at `index`.
+    fn inheritor_index(&self, index: u8) -> Result<usize, ProgramError> {
+        let index = index as usize;
+        if index >= self.inheritors.len() {
+            msg!("No inheritor {}, the will lists {}", index, self.inheritors.len());
+            return Err(WillError::InheritorIndexOutOfBounds.into());
+        }
+        Ok(index)
+    }
+
+    /// The inheritor at `index`, for an instruction changing them.
+    fn inheritor_mut(&mut self, index: u8) -> Result<&mut InheritorInfo, ProgramError> {
+        let index = self.inheritor_index(index)?;
+        Ok(&mut self.inheritors[index])
+    }
+
+    /// Once an inheritor claimed, which took the distribution snapshot, the owner can
+    /// not change the will before a heartbeat, not even after the inheritors pushed
+    /// the release back.
+    fn check_not_distributing(&self) -> Result<(), ProgramError> {
+        if self.snapshot_total_shares == 0 {
+            return Ok(())
+        }
+        msg!("Inheritors already claimed from the will, send a heartbeat before changing it");
+        Err(WillError::AlreadyReleased.into())
+    }
+
+    /// Inheritors can not be changed once the will is released or claimed from, a
+    /// heartbeat reopens it.
+    fn check_not_released(&self) -> Result<(), ProgramError> {
+        self.check_not_distributing()?;
+        let now = Clock::get()?.unix_timestamp;
+        if self.withdraw_allowed_ts > now {
+            return Ok(())
+        }
+        msg!("Contract was released at {}, send a heartbeat before changing it", self.withdraw_allowed_ts);
+        Err(WillError::AlreadyReleased.into())
+    }
+
+    /// Unclaimed shares of every inheritor together.
+    pub fn total_shares(&self) -> u64 {
+        self.inheritors.iter().map(|info| info.share as u64).sum()
+    }
+
+    /// Inheritors still holding a share, counting an inheritor listed more than once
+    /// a single time.
+    pub fn active_inheritor_count(&self) -> usize {
+        let mut active: Vec<&Pubkey> = self.inheritors.iter().filter(|info| info.share > 0).map(|info| &info.pubkey).collect();
+        active.sort();
+        active.dedup();
+        active.len()
+    }
+
+    /// Lamports paid out to inheritors so far.
+    pub fn claimed_total(&self) -> u64 {
+        self.inheritors.iter().fold(0_u64, |total, info| total.saturating_add(info.claimed))
+    }
+
+    /// Returns the fixed amount of `mint` of the entries at `indices`, and that of
+    /// every entry not yet fully claimed, see `compute_token_payout`. Fixed amounts
+    /// of other mints do not count.
+    pub fn fixed_amounts(&self, indices: &[usize], mint: &Pubkey) -> Result<(u64, u64), ProgramError> {
+        fn sum(mut amounts: impl Iterator<Item = u64>) -> Result<u64, ProgramError> {
+            amounts.try_fold(0_u64, u64::checked_add).ok_or_else(|| {
+                msg!("Fixed amounts overflow");
+                WillError::ArithmeticOverflow.into()
+            })
+        }
+        let of_mint = |info: &InheritorInfo| match info.fixed_amount {
+            Some((fixed_mint, amount)) if fixed_mint == *mint => Some(amount),
+            _ => None,
+        };
+        let fixed_amount = sum(indices.iter().filter_map(|&i| of_mint(&self.inheritors[i])))?;
+        let total_fixed = sum(self.inheritors.iter().filter(|info| info.share > 0).filter_map(of_mint))?;
+        Ok((fixed_amount, total_fixed))
+    }
+
+    /// Returns the unclaimed shares of `inheritor` at `now`, the unclaimed shares of
+    /// everyone, and the indices of every entry `inheritor` claims, so an inheritor
+    /// listed more than once receives all of their entries, and a contingent the
+    /// shares that lapsed to them, see `claimant`.
+    ///
+    /// Fails with `TotalSharesZero` if nobody holds a share any more, with
+    /// `ShareLapsed` if `inheritor`'s share passed to their contingent, with
+    /// `AlreadyClaimed` if `inheritor` is listed but their shares were zeroed by a
+    /// completed claim, and with `NotAnInheritor` if `inheritor` is not listed at
+    /// all, so the indices are never empty.
+    fn get_share(&self, inheritor: &Pubkey, now: UnixTimestamp) -> Result<(u64, u64, Vec<usize>), WillError> {
+        self.get_share_matching(|info| info.pubkey == *inheritor, now)
+    }
+
+    /// Like `get_share`, but finds the inheritor by the name the owner gave them,
+    /// so a front end can show a share without the inheritor's key at hand.
+    /// Names match exactly and case-sensitively, "Alice" is not "alice".
+    pub fn get_share_by_name(&self, name: &str, now: UnixTimestamp) -> Result<(u64, u64, Vec<usize>), WillError> {
+        self.get_share_matching(|info| info.name == name, now)
+    }
+
+    /// Sums the total and the matching entries in a single pass, calling `matches`
+    /// once per inheritor still holding a share, with whoever claims it at `now`.
+    fn get_share_matching(&self, matches: impl Fn(&InheritorInfo) -> bool, now: UnixTimestamp) -> Result<(u64, u64, Vec<usize>), WillError> {
+        let mut total_shares = 0_u64;
+        let mut inheritor_shares = 0_u64;
+        let mut found_indices = vec![];
+        for (i, info) in self.inheritors.iter().enumerate().filter(|(_, info)| info.share > 0) {
+            total_shares += info.share as u64;
+            if matches(&self.inheritors[self.claimant(i, now)]) {
+                inheritor_shares += info.share as u64;
+                found_indices.push(i);
+            }
+        }
+        if total_shares == 0 {
+            return Err(WillError::TotalSharesZero);
+        }
+        if found_indices.is_empty() {
+            if self.inheritors.iter().enumerate().any(|(i, info)| matches(info) && self.lapsed(i, now)) {
+                return Err(WillError::ShareLapsed);
+            }
+            // Entries are only zeroed by completed claims, anyone still listed was paid.
+            if self.inheritors.iter().any(&matches) {
+                return Err(WillError::AlreadyClaimed);
+            }
+            return Err(WillError::NotAnInheritor);
+        }
+        Ok((inheritor_shares, total_shares, found_indices))
+    }
+
+    /// Lamports paid to `inheritor` so far, over every entry they are listed under.
+    pub fn get_claimed(&self, inheritor: &Pubkey) -> u64 {
+        let mut claimed = 0_u64;
+        for info in self.inheritors.iter().filter(|info| info.pubkey == *inheritor) {
+            claimed = claimed.saturating_add(info.claimed);
+        }
+        claimed
+    }
+
+    /// Index of the inheritor named as the contingent of the inheritor at `index`.
+    pub fn contingent(&self, index: usize) -> Option<usize> {
+        self.inheritors.iter().position(|info| info.contingent_of.map(usize::from) == Some(index))
+    }
+
+    /// Whether the share at `index` lapsed by `now`: the inheritor has a contingent
+    /// and still held the share, but was paid nothing of it `CONTINGENCY_SECONDS`
+    /// after the release. Once a claim recorded the lapse, what the contingent is
+    /// paid of the share does not give it back to the inheritor.
+    pub fn lapsed(&self, index: usize, now: UnixTimestamp) -> bool {
+        let info = &self.inheritors[index];
+        self.contingent(index).is_some() && (info.lapsed || (info.share > 0 && info.claimed == 0
+            && now >= self.withdraw_allowed_ts.saturating_add(CONTINGENCY_SECONDS)))
+    }
+
+    /// Index of the inheritor who claims the share at `index` at `now`: the
+    /// contingent once the share lapsed, otherwise the inheritor listed for it.
+    pub fn claimant(&self, index: usize, now: UnixTimestamp) -> usize {
+        match self.contingent(index) {
+            Some(contingent) if self.lapsed(index, now) => contingent,
+            _ => index,
+        }
+    }
+
+    /// Records the shares that lapsed by `now` and were not recorded yet, and
+    /// returns their (inheritor, contingent) pairs. Claims record lapses before
+    /// they pay anything, the entries keep their inheritor and delegate.
+    pub fn record_lapses(&mut self, now: UnixTimestamp) -> Vec<(Pubkey, Pubkey)> {
+        let mut lapses = vec![];
+        for index in 0..self.inheritors.len() {
+            if !self.inheritors[index].lapsed && self.lapsed(index, now) {
+                lapses.push((self.inheritors[index].pubkey, self.inheritors[self.claimant(index, now)].pubkey));
+                self.inheritors[index].lapsed = true;
+            }
+        }
+        lapses
+    }
+
+    /// Lamports distributed between the inheritors and the shares they are split
+    /// by: the snapshot once a claim took it, otherwise the `balance` plus whatever
+    /// the inheritors still holding shares were already paid.
+    fn distribution(&self, balance: u64) -> (u64, u64) {
+        if self.snapshot_total_shares > 0 {
+            return (self.distributable_snapshot, self.snapshot_total_shares)
+        }
+        let mut pool = balance;
+        for info in self.inheritors.iter().filter(|info| info.share > 0) {
+            pool = pool.saturating_add(info.claimed);
+        }
+        (pool, self.total_shares())
+    }
+
+    /// Fixes what the inheritors split to the will's `balance` at the first claim,
+    /// so every inheritor receives the same lamports whatever order they claim in.
+    /// A heartbeat drops the snapshot again.
+    fn snapshot_distribution(&mut self, balance: u64) {
+        if self.snapshot_total_shares == 0 {
+            let (pool, total_shares) = self.distribution(balance);
+            self.distributable_snapshot = pool;
+            self.snapshot_total_shares = total_shares;
+        }
+    }
+
+    /// Lamports `inheritor` may claim out of the will's `balance` at `now`, and
+    /// whether that claim completes their share.
+    ///
+    /// An inheritor is entitled to their share of the distribution, see
+    /// `distribution`, so partial claims do not move lamports between inheritors.
+    /// Of that, the part vested by `now` less what they already received is payable.
+    ///
+    /// Payouts round down. Rounding leftovers and lamports sent to the will after
+    /// the snapshot stay in it until the last inheritor completes their claim, who
+    /// takes them as the will is closed.
+    fn claimable(&self, inheritor: &Pubkey, balance: u64, now: UnixTimestamp) -> Result<(u64, bool), ProgramError> {
+        let (inheritor_shares, _, inheritor_indices) = self.get_share(inheritor, now)?;
+        let (pool, total_shares) = self.distribution(balance);
+        // Only entries still open count, a contingent's own completed entry does
+        // not hold back the share they took over.
+        let claimed = inheritor_indices.iter().fold(0_u64, |sum, i| sum.saturating_add(self.inheritors[*i].claimed));
+        let entitled = compute_payout(pool, inheritor_shares, total_shares, 0)?;
+
+        let elapsed = now.saturating_sub(self.withdraw_allowed_ts).max(0);
+        let vested = if elapsed >= self.vesting_seconds {
+            entitled
+        } else {
+            (entitled as u128 * elapsed as u128 / self.vesting_seconds as u128) as u64
+        };
+        Ok((vested.saturating_sub(claimed), vested == entitled))
+    }
+}
+
+
+entrypoint!(process_instruction);
+
+/// Entrypoint of the will program, see `Processor::process`.
+pub fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+    Processor::process(program_id, accounts, instruction_data)
+}
+
+/// Release of a will as returned by `QueryReleaseStatus`, borsh encoded.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct ReleaseStatus {
+    pub released: bool,
+    pub withdraw_allowed_ts: UnixTimestamp,
+    pub seconds_remaining: i64,  // 0 once released.
+}
+
+/// Address that owns the will's SPL token accounts and signs transfers out of them.
+pub fn find_token_authority(will: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TOKEN_AUTHORITY_SEED, will.as_ref()], program_id)
+}
+
+/// Part of `balance` owed to an inheritor holding `inheritor_shares` out of
+/// `total_shares`, after setting aside `reserve`. A balance at or below the
+/// reserve pays nothing.
+///
+/// Multiplies before dividing in `u128`, so large balances can not overflow, and
+/// rounds down. The truncated remainder stays in the account, and since claimed
+/// shares drop out of `total_shares`, the last inheritor to claim collects it.
+/// Works for token amounts just as for lamports.
+pub fn compute_payout(balance: u64, inheritor_shares: u64, total_shares: u64, reserve: u64) -> Result<u64, ProgramError> {
+    if total_shares == 0 {
+        msg!("No shares to pay out");
+        return Err(WillError::TotalSharesZero.into());
+    }
+    if inheritor_shares > total_shares {
+        msg!("Inheritor shares {} exceed the total of {}", inheritor_shares, total_shares);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let payable = balance.saturating_sub(reserve);
+    Ok((payable as u128 * inheritor_shares as u128 / total_shares as u128) as u64)
+}
+
+/// Computes the tokens an inheritor completing their claim receives out of a will
+/// token account holding `balance`.
+///
+/// Fixed amounts come first: the inheritor receives their own `fixed_amount` in
+/// full, plus their share of what is left once the fixed amounts of everyone yet
+/// to claim, `total_fixed`, are set aside. Should the fixed amounts exceed the
+/// balance, it is split in proportion to them and nothing is left to share.
+pub fn compute_token_payout(
+    balance: u64,
+    inheritor_shares: u64,
+    total_shares: u64,
+    fixed_amount: u64,
+    total_fixed: u64,
+) -> Result<u64, ProgramError> {
+    if total_fixed > balance {
+        msg!("Fixed amounts of {} exceed the balance of {}", total_fixed, balance);
+        return compute_payout(balance, fixed_amount, total_fixed, 0);
+    }
+    // At most `balance - total_fixed`, so adding the fixed amount can not overflow.
+    Ok(fixed_amount + compute_payout(balance, inheritor_shares, total_shares, total_fixed)?)
+}
+
+/// Moves lamports between accounts, leaving both untouched if either side would wrap.
+///
+/// Lamports leave wills by changing balances directly rather than through
+/// `system_instruction::transfer`: the System Program only debits accounts it owns
+/// that carry no data, while a will is owned by this program and holds its data,
+/// and its address comes from `create_with_seed`, so it is no program address this
+/// program could sign for either. Any account may be credited, including ones this
+/// program does not own.
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    let from_lamports = from.lamports().checked_sub(lamports).ok_or_else(|| {
+        msg!("Account {} holds {} lamports, can not transfer {}", from.key, from.lamports(), lamports);
+        ProgramError::InsufficientFunds
+    })?;
+    let to_lamports = to.lamports().checked_add(lamports).ok_or_else(|| {
+        msg!("Crediting {} lamports to {} overflows its balance", lamports, to.key);
+        ProgramError::from(WillError::ArithmeticOverflow)
+    })?;
+    **from.try_borrow_mut_lamports()? = from_lamports;
+    **to.try_borrow_mut_lamports()? = to_lamports;
+    Ok(())
+}
+
+/// Time the will is released at if the owner shows no sign of life from now on.
+///
+/// Fails rather than wrapping into the past, which would release the will at once.
+fn release_time(timeout_seconds: i64) -> Result<UnixTimestamp, ProgramError> {
+    let now = Clock::get()?.unix_timestamp;
+    now.checked_add(timeout_seconds).ok_or_else(|| {
+        msg!("Timeout of {} seconds from {} overflows", timeout_seconds, now);
+        WillError::ArithmeticOverflow.into()
+    })
+}
+
+fn check_timeout(timeout_seconds: i64) -> ProgramResult {
+    if !(MIN_TIMEOUT_SECONDS..=MAX_TIMEOUT_SECONDS).contains(&timeout_seconds) {
+        msg!("Timeout of {} seconds is outside of [{}, {}]", timeout_seconds, MIN_TIMEOUT_SECONDS, MAX_TIMEOUT_SECONDS);
+        return Err(WillError::TimeoutOutOfBounds.into());
+    }
+    Ok(())
+}
+
+/// Programs of the runtime this program can never be deployed as. Seeing one as
+/// `program_id` means a misconfigured caller, and wills derived under it by
+/// `create_with_seed` would not be this program's.
+fn reserved_program_ids() -> [Pubkey; 6] {
+    [
+        system_program::id(),
+        sysvar::id(),
+        solana_program::bpf_loader::id(),
+        solana_program::bpf_loader_deprecated::id(),
+        solana_program::bpf_loader_upgradeable::id(),
+        spl_token::id(),
+    ]
+}
+
+fn check_program_id(program_id: &Pubkey) -> ProgramResult {
+    if reserved_program_ids().contains(program_id) {
+        msg!("Program id {} is reserved for another program", program_id);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+fn check_signer(sender: &AccountInfo) -> Result<(), ProgramError> {
+    if !sender.is_signer {
+        msg!("Sender {} did not sign the transaction", sender.key);
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+/// Seed the address of a will stored with the given layout version is derived with.
+///
+/// Addresses never move, so every layout read by this program, legacy ones included,
+/// maps to the "v3" seed its wills were created under. Only layouts newer than
+/// `SCHEMA_VERSION` would be created under a seed of their own.
+pub fn will_seed(schema_version: u8) -> String {
+    match schema_version {
+        0..=SCHEMA_VERSION => WILL_SEED.to_string(),
+        newer => format!("solana-will.com/my/v{}/1", newer),
+    }
+}
+
+/// Address of the will `owner` creates with `Pubkey::create_with_seed`, the one
+/// `check_ownership` accepts for a will in the current layout.
+pub fn derive_will_address(owner: &Pubkey, program_id: &Pubkey) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_with_seed(owner, &will_seed(SCHEMA_VERSION), program_id)
+}
+
+/// Checks that `account` is the will `sender_key` creates, see `derive_will_address`.
+///
+/// The address only constrains creating a will and adopting one from before the
+/// owner was stored. From then on the stored owner decides, see `WillData::check_owner`.
+fn check_ownership(account: &AccountInfo, sender_key: &Pubkey, program_id: &Pubkey) -> Result<(), ProgramError> {
+    let schema_version = account.data.borrow().first().copied().unwrap_or(SCHEMA_VERSION);
+    let seed = &will_seed(schema_version);
+    let expected_account = Pubkey::create_with_seed(sender_key, seed, program_id).map_err(|err| {
+        msg!("No will address derives from {} with seed {}: {}", sender_key, seed, err);
+        WillError::WillAddressUnderivable
+    })?;
+    if *account.key != expected_account {
+        msg!("Sender {} with seed {} should be {}", sender_key, seed, expected_account);
+        msg!("But got {}", account.key);
+        return Err(WillError::WillAddressMismatch.into());
+    }
+    Ok(())
+}
+
+// Sanity tests
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use solana_program::{clock::Epoch, entrypoint::SUCCESS, instruction::Instruction, program_stubs, rent::Rent};
+    use solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE;
+    use std::{cell::{Cell, RefCell}, convert::TryInto, mem, sync::Once};
+
+    thread_local! {
+        static NOW: Cell<UnixTimestamp> = Cell::new(0);
+        static LOGS: RefCell<Vec<String>> = RefCell::new(vec![]);
+        static RETURN_DATA: RefCell<Vec<u8>> = RefCell::new(vec![]);
+        static EVENTS: RefCell<Vec<Vec<u8>>> = RefCell::new(vec![]);
+        static INVOKE: RefCell<Option<Box<InvokeHandler>>> = RefCell::new(None);
+    }
+
+    type InvokeHandler = dyn Fn(&Instruction, &[AccountInfo]) -> ProgramResult;
+
+    struct TestSyscallStubs;
+
+    impl program_stubs::SyscallStubs for TestSyscallStubs {
+        fn sol_log(&self, message: &str) {
+            LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+        }
+
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock {
+                unix_timestamp: NOW.with(|now| now.get()),
+                ..Clock::default()
+            };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+
+        fn sol_set_return_data(&self, data: &[u8]) {
+            RETURN_DATA.with(|return_data| *return_data.borrow_mut() = data.to_vec());
+        }
+
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().push(fields.concat()));
+        }
+
+        fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], _signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+            INVOKE.with(|invoke| match &*invoke.borrow() {
+                Some(invoke) => invoke(instruction, account_infos),
+                None => {
+                    msg!("SyscallStubs: sol_invoke_signed() not available");
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    /// Makes `Clock::get()` report `unix_timestamp` on the current test thread.
+    pub(crate) fn set_clock(unix_timestamp: UnixTimestamp) {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+        NOW.with(|now| now.set(unix_timestamp));
+    }
+
+    /// Runs `invoke` in place of every program the current test thread invokes, `None`
+    /// invokes nothing again.
+    pub(crate) fn set_invoke(invoke: Option<Box<InvokeHandler>>) {
+        INVOKE.with(|handler| *handler.borrow_mut() = invoke);
+    }
+
+    /// Drains the messages logged on the current test thread.
+    pub(crate) fn take_logs() -> Vec<String> {
+        LOGS.with(|logs| logs.borrow_mut().drain(..).collect())
+    }
+
+    /// Takes the return data last set on the current test thread.
+    pub(crate) fn take_return_data() -> Vec<u8> {
+        RETURN_DATA.with(|return_data| mem::take(&mut *return_data.borrow_mut()))
+    }
+
+    /// Drains and decodes the events logged on the current test thread.
+    pub(crate) fn take_events() -> Vec<crate::event::WillEvent> {
+        EVENTS.with(|events| {
+            events.borrow_mut().drain(..)
+                .map(|data| crate::event::WillEvent::try_from_slice(&data).unwrap())
+                .collect()
+        })
+    }
+
+    pub(crate) fn serialized_will(inheritors: &[Pubkey], shares: &[u32], withdraw_allowed_ts: UnixTimestamp) -> Vec<u8> {
+        let will_data = WillData {
+            schema_version: SCHEMA_VERSION,
+            withdraw_allowed_ts,
+            inheritors: inheritors.iter().zip(shares)
+                .map(|(pubkey, share)| InheritorInfo { name: String::from("heir"), pubkey: *pubkey, share: *share, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false })
+                .collect(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: Pubkey::default(),
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: withdraw_allowed_ts - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+            distributable_snapshot: 0,
+            snapshot_total_shares: 0,
+            max_withdraw_per_period: None,
+            last_withdraw_ts: 0,
+            withdrawn_in_period: 0,
+            pending_withdraw_cap: None,
+            extensions: 0,
+            token_accounts: vec![],
+        };
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        data
+    }
+
+    pub(crate) struct TestAccount {
+        pub(crate) key: Pubkey,
+        pub(crate) is_signer: bool,
+        pub(crate) lamports: u64,
+        pub(crate) data: Vec<u8>,
+        pub(crate) owner: Pubkey,
+    }
+
+    impl TestAccount {
+        pub(crate) fn wallet(lamports: u64) -> Self {
+            Self { key: Pubkey::new_unique(), is_signer: true, lamports, data: vec![], owner: Pubkey::default() }
+        }
+
+        pub(crate) fn will(key: Pubkey, program_id: &Pubkey, lamports: u64, data: Vec<u8>) -> Self {
+            Self { key, is_signer: false, lamports, data, owner: *program_id }
+        }
+
+        pub(crate) fn info(&mut self) -> AccountInfo {
+            AccountInfo::new(&self.key, self.is_signer, true, &mut self.lamports, &mut self.data, &self.owner, false, Epoch::default())
+        }
+    }
+
+    pub(crate) fn will_address(owner: &Pubkey, program_id: &Pubkey) -> Pubkey {
+        derive_will_address(owner, program_id).unwrap()
+    }
+
+    /// Runs the program over `accounts`, laying their data out the way the runtime
+    /// does, length first and room to grow after, so `realloc` works natively.
+    pub(crate) fn process(program_id: &Pubkey, accounts: &mut [&mut TestAccount], instruction_data: &[u8]) -> ProgramResult {
+        let mut buffers: Vec<Vec<u8>> = accounts.iter().map(|account| {
+            let mut buffer = (account.data.len() as u64).to_le_bytes().to_vec();
+            buffer.extend_from_slice(&account.data);
+            buffer.resize(buffer.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+            buffer
+        }).collect();
+        let result = {
+            let infos: Vec<AccountInfo> = accounts.iter_mut().zip(buffers.iter_mut())
+                .map(|(account, buffer)| {
+                    let data = &mut buffer[8..8 + account.data.len()];
+                    AccountInfo::new(&account.key, account.is_signer, true, &mut account.lamports, data, &account.owner, false, Epoch::default())
+                })
+                .collect();
+            process_instruction(program_id, &infos, instruction_data)
+        };
+        for (account, buffer) in accounts.iter_mut().zip(buffers) {
+            let len = u64::from_le_bytes(buffer[..8].try_into().unwrap()) as usize;
+            account.data = buffer[8..8 + len].to_vec();
+        }
+        result
+    }
+
+    fn set_inheritance(program_id: &Pubkey, owner: &mut TestAccount, will: &mut TestAccount, names: &[&str], pubkeys: &[Pubkey], shares: &[u16]) -> ProgramResult {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let instruction_data = (0_u8, names, pubkeys.to_vec(), shares.to_vec(), DEFAULT_TIMEOUT_SECONDS, 0_i64).try_to_vec().unwrap();
+        process(program_id, &mut [owner, will], &instruction_data)
+    }
+
+    fn set_inheritance_ppm(program_id: &Pubkey, owner: &mut TestAccount, will: &mut TestAccount, names: &[&str], pubkeys: &[Pubkey], shares: &[u32]) -> ProgramResult {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        let instruction_data = (15_u8, names, pubkeys.to_vec(), shares.to_vec(), DEFAULT_TIMEOUT_SECONDS, 0_i64).try_to_vec().unwrap();
+        process(program_id, &mut [owner, will], &instruction_data)
+    }
+
+    fn names_of(will_data: &WillData) -> Vec<String> {
+        will_data.inheritors.iter().map(|info| info.name.clone()).collect()
+    }
+
+    fn pubkeys_of(will_data: &WillData) -> Vec<Pubkey> {
+        will_data.inheritors.iter().map(|info| info.pubkey).collect()
+    }
+
+    fn shares_of(will_data: &WillData) -> Vec<u32> {
+        will_data.inheritors.iter().map(|info| info.share).collect()
+    }
+
+    fn claimed_of(will_data: &WillData) -> Vec<u64> {
+        will_data.inheritors.iter().map(|info| info.claimed).collect()
+    }
+
+    fn claim(program_id: &Pubkey, will: &mut TestAccount, inheritor: &Pubkey) -> Result<u64, ProgramError> {
+        let mut sender = TestAccount::wallet(0);
+        sender.key = *inheritor;
+        process(program_id, &mut [&mut sender, will], &[2])?;
+        Ok(sender.lamports)
+    }
+
+    #[test]
+    fn test_sanity() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let key = will_address(&owner_key, &program_id);
+        let will_data = WillData {
+            schema_version: SCHEMA_VERSION,
+            withdraw_allowed_ts: 1_000 + DEFAULT_TIMEOUT_SECONDS,
+            inheritors: vec![InheritorInfo { name: String::from("heir"), pubkey: Pubkey::new_unique(), share: 10_000, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false }],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner_key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 1_000,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+            distributable_snapshot: 0,
+            snapshot_total_shares: 0,
+            max_withdraw_per_period: None,
+            last_withdraw_ts: 0,
+            withdrawn_in_period: 0,
+            pending_withdraw_cap: None,
+            extensions: 0,
+            token_accounts: vec![],
+        };
+        let mut owner_lamports = 0;
+        let mut owner_data = vec![];
+        let mut lamports = 10_000_000;
+        let mut data = will_data.try_to_vec().unwrap();
+        let system_program = Pubkey::default();
+        let owner = AccountInfo::new(
+            &owner_key,
+            true,
+            true,
+            &mut owner_lamports,
+            &mut owner_data,
+            &system_program,
+            false,
+            Epoch::default(),
+        );
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+            false,
+            Epoch::default(),
+        );
+        // 4 - heartbeat
+        let instruction_data: Vec<u8> = vec![4];
+
+        let accounts = vec![owner, account];
+
+        assert_eq!(WillData::unpack(&accounts[1].data.borrow()).unwrap().withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        set_clock(1_100);
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        assert_eq!(
+            WillData::unpack(&accounts[1].data.borrow())
+                .unwrap()
+                .withdraw_allowed_ts,
+            1_100 + DEFAULT_TIMEOUT_SECONDS
+        );
+        set_clock(1_200);
+        process_instruction(&program_id, &accounts, &instruction_data).unwrap();
+        let stored = WillData::unpack(&accounts[1].data.borrow()).unwrap();
+        assert_eq!(stored.withdraw_allowed_ts, 1_200 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(stored.inheritors, will_data.inheritors);
+        assert_eq!(stored.owner, owner_key);
+        assert_eq!(accounts[1].lamports(), 10_000_000);
+    }
+
+    #[test]
+    fn test_compute_payout_rounds_down() {
+        assert_eq!(compute_payout(10_000, 3333, 10_000, 0), Ok(3333));
+        assert_eq!(compute_payout(10, 1, 3, 0), Ok(3));
+        assert_eq!(compute_payout(u64::MAX, 9999, 10_000, 0), Ok((u64::MAX as u128 * 9999 / 10_000) as u64));
+        assert_eq!(compute_payout(u64::MAX, 10_000, 10_000, 0), Ok(u64::MAX));
+        assert_eq!(compute_payout(u64::MAX, 1, 1_000_000, 0), Ok(u64::MAX / 1_000_000));
+    }
+
+    #[test]
+    fn test_compute_payout_sets_aside_the_reserve() {
+        assert_eq!(compute_payout(10_000, 5000, 10_000, 2_000), Ok(4_000));
+        assert_eq!(compute_payout(2_000, 5000, 10_000, 2_000), Ok(0));
+        assert_eq!(compute_payout(1_000, 5000, 10_000, 2_000), Ok(0));
+        assert_eq!(compute_payout(u64::MAX, 1, 1, u64::MAX - 1), Ok(1));
+    }
+
+    #[test]
+    fn test_compute_token_payout_pays_fixed_amounts_first() {
+        // Bob gets 1000 on top of his half of what is left, then Alice takes the rest.
+        assert_eq!(compute_token_payout(3_000, 5000, 10_000, 1_000, 1_000), Ok(2_000));
+        assert_eq!(compute_token_payout(1_000, 5000, 5000, 0, 0), Ok(1_000));
+        // Alice claiming first leaves Bob's fixed amount untouched.
+        assert_eq!(compute_token_payout(3_000, 5000, 10_000, 0, 1_000), Ok(1_000));
+        assert_eq!(compute_token_payout(2_000, 5000, 5000, 1_000, 1_000), Ok(2_000));
+        // Fixed amounts exactly exhausting the balance leave nothing to share.
+        assert_eq!(compute_token_payout(1_000, 5000, 10_000, 0, 1_000), Ok(0));
+    }
+
+    #[test]
+    fn test_compute_token_payout_over_allocated() {
+        assert_eq!(compute_token_payout(500, 5000, 10_000, 1_000, 2_000), Ok(250));
+        assert_eq!(compute_token_payout(250, 5000, 5000, 1_000, 1_000), Ok(250));
+        assert_eq!(compute_token_payout(500, 5000, 10_000, 0, 2_000), Ok(0));
+        assert_eq!(compute_token_payout(u64::MAX - 1, 1, 1, u64::MAX, u64::MAX), Ok(u64::MAX - 1));
+    }
+
+    #[test]
+    fn test_compute_payout_edge_cases() {
+        assert_eq!(compute_payout(10_000, 0, 10_000, 0), Ok(0));
+        assert_eq!(compute_payout(0, 5000, 10_000, 0), Ok(0));
+        assert_eq!(compute_payout(10_000, 0, 0, 0), Err(WillError::TotalSharesZero.into()));
+        assert_eq!(compute_payout(10_000, 2, 1, 0), Err(ProgramError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_claims_pay_out_whole_balance() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let will_data = serialized_will(&inheritors, &[3333, 3333, 3334], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+
+        // No rent-exempt reserve is held back, so the whole balance is distributable.
+        let distributable = will.lamports;
+        let mut paid = vec![];
+        for inheritor in inheritors.iter() {
+            paid.push(claim(&program_id, &mut will, inheritor).unwrap());
+        }
+        assert_eq!(paid, vec![3333, 3333, 3334]);
+        assert_eq!(paid.iter().sum::<u64>(), distributable);
+        assert_eq!(will.lamports, 0);
+    }
+
+    #[test]
+    fn test_claim_from_zeroed_account() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, vec![0; 1024]);
+
+        assert_eq!(
+            claim(&program_id, &mut will, &Pubkey::new_unique()),
+            Err(WillError::TotalSharesZero.into())
+        );
+        assert_eq!(will.lamports, 10_000);
+    }
+
+    #[test]
+    fn test_empty_instruction_data() {
+        let program_id = Pubkey::new_unique();
+        assert_eq!(
+            process_instruction(&program_id, &[], &[]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_unknown_selector() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_key = will_address(&owner.key, &program_id);
+        let will_data = serialized_will(&[], &[], 0);
+        let mut will = TestAccount::will(will_key, &program_id, 10_000, will_data.clone());
+
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &[99]),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(will.data, will_data);
+        assert_eq!(will.lamports, 10_000);
+    }
+
+    #[test]
+    fn test_swapped_accounts_are_rejected() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_key = will_address(&owner.key, &program_id);
+        let will_data = serialized_will(&[], &[], 0);
+        let mut will = TestAccount::will(will_key, &program_id, 10_000, will_data.clone());
+
+        assert_eq!(
+            process(&program_id, &mut [&mut will, &mut owner], &[4]),
+            Err(WillError::SenderNotAWallet.into())
+        );
+        assert_eq!(take_logs().pop().unwrap(), format!("Sender {} is a will, the sender must come first and the will second", will_key));
+
+        let mut token_account = TestAccount::wallet(0);
+        token_account.owner = spl_token::id();
+        assert_eq!(
+            process(&program_id, &mut [&mut token_account, &mut will], &[4]),
+            Err(WillError::SenderNotAWallet.into())
+        );
+        assert_eq!(will.data, will_data);
+        assert_eq!(will.lamports, 10_000);
+    }
+
+    #[test]
+    fn test_owner_instructions_require_signature() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        owner.is_signer = false;
+        let will_key = will_address(&owner.key, &program_id);
+        let mut will = TestAccount::will(will_key, &program_id, 10_000, serialized_will(&[], &[], 0));
+
+        let set_inheritance = (0_u8, vec![String::from("heir")], vec![Pubkey::new_unique()], vec![10_000_u16], DEFAULT_TIMEOUT_SECONDS, 0_i64);
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &set_inheritance.try_to_vec().unwrap()),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        let withdraw = (1_u8, 1_000_u64);
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &withdraw.try_to_vec().unwrap()),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(will.lamports, 10_000);
+        assert_eq!(owner.lamports, 0);
+    }
+
+    #[test]
+    fn test_claim_requires_signature() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        inheritor.is_signer = false;
+        let will_data = serialized_will(&[inheritor.key], &[10_000], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+
+        assert_eq!(
+            process(&program_id, &mut [&mut inheritor, &mut will], &[2]),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(will.lamports, 10_000);
+    }
+
+    #[test]
+    fn test_withdraw_keeps_rent_exempt_reserve() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[], &[], 0);
+        let rent_reserve = Rent::default().minimum_balance(will_data.len());
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, rent_reserve + 1_000, will_data);
+
+        let withdraw = (1_u8, 1_000_u64);
+        process(&program_id, &mut [&mut owner, &mut will], &withdraw.try_to_vec().unwrap()).unwrap();
+        assert_eq!(will.lamports, rent_reserve);
+        assert_eq!(owner.lamports, 1_000);
+
+        let withdraw = (1_u8, 1_u64);
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &withdraw.try_to_vec().unwrap()),
+            Err(WillError::BelowRentExemptReserve.into())
+        );
+        assert_eq!(will.lamports, rent_reserve);
+        assert_eq!(owner.lamports, 1_000);
+    }
+
+    #[test]
+    fn test_withdraw_returns_lamports() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 1_000_000_000, serialized_will(&[], &[], 0));
+
+        let withdraw = (1_u8, 1_234_u64);
+        process(&program_id, &mut [&mut owner, &mut will], &withdraw.try_to_vec().unwrap()).unwrap();
+        assert_eq!(take_return_data(), 1_234_u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_claim_returns_lamports() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&inheritors, &[2_500, 7_500], 0));
+
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(2_500));
+        assert_eq!(take_return_data(), 2_500_u64.to_le_bytes());
+
+        assert!(claim(&program_id, &mut will, &inheritors[0]).is_err());
+        assert!(take_return_data().is_empty());
+    }
+
+    #[test]
+    fn test_transfer_lamports_does_not_wrap() {
+        let mut from = TestAccount::wallet(10);
+        let mut to = TestAccount::wallet(u64::MAX - 5);
+        {
+            let (from_info, to_info) = (from.info(), to.info());
+            assert_eq!(transfer_lamports(&from_info, &to_info, 11), Err(ProgramError::InsufficientFunds));
+            assert_eq!(transfer_lamports(&from_info, &to_info, 6), Err(WillError::ArithmeticOverflow.into()));
+            transfer_lamports(&from_info, &to_info, 5).unwrap();
+        }
+        assert_eq!(from.lamports, 5);
+        assert_eq!(to.lamports, u64::MAX);
+    }
+
+    #[test]
+    fn test_over_withdraw_is_rejected() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 0));
+
+        let withdraw = (1_u8, 10_001_u64);
+        assert!(process(&program_id, &mut [&mut owner, &mut will], &withdraw.try_to_vec().unwrap()).is_err());
+        assert_eq!(will.lamports, 10_000);
+        assert_eq!(owner.lamports, 0);
+    }
+
+    #[test]
+    fn test_claim_overflowing_inheritor_balance_is_rejected() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(u64::MAX);
+        let will_data = serialized_will(&[inheritor.key], &[10_000], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+
+        assert_eq!(
+            process(&program_id, &mut [&mut inheritor, &mut will], &[2]),
+            Err(WillError::ArithmeticOverflow.into())
+        );
+        assert_eq!(will.lamports, 10_000);
+        assert_eq!(inheritor.lamports, u64::MAX);
+    }
+
+    #[test]
+    fn test_get_share_matches_pubkey() {
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let will_data = WillData::unpack(&serialized_will(&inheritors, &[2500, 7500], 0)).unwrap();
+        assert_eq!(will_data.get_share(&inheritors[0], 0), Ok((2500, 10_000, vec![0])));
+        assert_eq!(will_data.get_share(&inheritors[1], 0), Ok((7500, 10_000, vec![1])));
+        assert_eq!(will_data.get_share(&Pubkey::new_unique(), 0), Err(WillError::NotAnInheritor));
+    }
+
+    #[test]
+    fn test_get_share_by_name() {
+        let mut will_data = WillData::unpack(&serialized_will(&[Pubkey::new_unique(); 3], &[2500, 1500, 6000], 0)).unwrap();
+        will_data.inheritors[0].name = String::from("Alice");
+        will_data.inheritors[1].name = String::from("Bob");
+        will_data.inheritors[2].name = String::from("Alice");
+        assert_eq!(will_data.get_share_by_name("Alice", 0), Ok((8500, 10_000, vec![0, 2])));
+        assert_eq!(will_data.get_share_by_name("Bob", 0), Ok((1500, 10_000, vec![1])));
+        assert_eq!(will_data.get_share_by_name("alice", 0), Err(WillError::NotAnInheritor));
+        assert_eq!(will_data.get_share_by_name("Carol", 0), Err(WillError::NotAnInheritor));
+        will_data.inheritors[1].share = 0;
+        assert_eq!(will_data.get_share_by_name("Bob", 0), Err(WillError::AlreadyClaimed));
+    }
+
+    #[test]
+    fn test_get_share_tells_claimed_from_strangers() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will_data = WillData::unpack(&serialized_will(&[alice, bob, alice], &[2500, 5000, 2500], 0)).unwrap();
+        will_data.inheritors[0].share = 0;
+        // One of two entries paid out still leaves a share to claim.
+        assert_eq!(will_data.get_share(&alice, 0), Ok((2500, 7500, vec![2])));
+        will_data.inheritors[2].share = 0;
+        assert_eq!(will_data.get_share(&alice, 0), Err(WillError::AlreadyClaimed));
+        assert_eq!(will_data.get_share(&Pubkey::new_unique(), 0), Err(WillError::NotAnInheritor));
+        assert_eq!(will_data.get_share(&bob, 0), Ok((5000, 5000, vec![1])));
+    }
+
+    #[test]
+    fn test_unpack_rejects_corrupt_wills() {
+        let v5 = WillDataV5 {
+            schema_version: 5,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            inheritors_shares: vec![4000, 6000],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: Pubkey::new_unique(),
+            inheritors_claimed: vec![0],
+            vesting_seconds: 0,
+        };
+        assert_eq!(WillData::unpack(&v5.try_to_vec().unwrap()).err(), Some(WillError::InheritorsLengthMismatch.into()));
+
+        let corrupt = |corrupt: &dyn Fn(&mut WillData)| {
+            let mut will_data = WillData::unpack(&serialized_will(&[Pubkey::new_unique()], &[10_000], 0)).unwrap();
+            corrupt(&mut will_data);
+            WillData::unpack(&will_data.try_to_vec().unwrap()).err()
+        };
+        assert_eq!(corrupt(&|_| ()), None);
+        assert_eq!(
+            corrupt(&|will_data| will_data.inheritors = vec![will_data.inheritors[0].clone(); MAX_INHERITORS + 1]),
+            Some(WillError::TooManyInheritors.into())
+        );
+        assert_eq!(corrupt(&|will_data| will_data.inheritors[0].share = 10_001), Some(WillError::ShareSumExceeded.into()));
+        assert_eq!(corrupt(&|will_data| will_data.max_total_shares = 0), Some(WillError::ShareSumExceeded.into()));
+        assert_eq!(
+            corrupt(&|will_data| will_data.guardians = vec![Pubkey::new_unique(); MAX_GUARDIANS + 1]),
+            Some(WillError::TooManyGuardians.into())
+        );
+        assert_eq!(
+            corrupt(&|will_data| will_data.required_confirmations = 1),
+            Some(WillError::ConfirmationsOutOfBounds.into())
+        );
+        assert_eq!(
+            corrupt(&|will_data| will_data.confirmations = vec![Pubkey::new_unique()]),
+            Some(WillError::ConfirmationsOutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn test_instructions_reject_corrupt_wills() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        let mut will_data = WillData::unpack(&serialized_will(&[inheritor.key], &[10_000], 0)).unwrap();
+        will_data.inheritors[0].share = 20_000;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000_000, data.clone());
+
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[2]), Err(WillError::ShareSumExceeded.into()));
+        assert_eq!((inheritor.lamports, will.lamports), (0, 10_000_000));
+        assert_eq!(will.data, data);
+    }
+
+    #[test]
+    fn test_get_share_makes_one_pass() {
+        // The compute a claim spends finding its share grows with the matcher calls,
+        // a full will of 32 inheritors costs one call per inheritor holding a share.
+        let mut will_data = WillData::unpack(&serialized_will(&[], &[], 0)).unwrap();
+        will_data.inheritors = (0..MAX_INHERITORS)
+            .map(|_| InheritorInfo { name: String::from("heir"), pubkey: Pubkey::new_unique(), share: 300, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false })
+            .collect();
+        will_data.inheritors[7].share = 0;
+        let last = will_data.inheritors[MAX_INHERITORS - 1].pubkey;
+
+        let calls = Cell::new(0);
+        let share = will_data.get_share_matching(|info| {
+            calls.set(calls.get() + 1);
+            info.pubkey == last
+        }, 0);
+        assert_eq!(share, Ok((300, 300 * (MAX_INHERITORS as u64 - 1), vec![MAX_INHERITORS - 1])));
+        assert_eq!(calls.get(), MAX_INHERITORS - 1);
+    }
+
+    #[test]
+    fn test_serialized_size_uses_fixed_width_keys() {
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let will_data = WillData::unpack(&serialized_will(&inheritors, &[1, 2, 3], 0)).unwrap();
+        let inheritor_len = (4 + "heir".len()) + 32 + 4 + 8 + 1 + 1 + 1 + 1;
+        assert_eq!(will_data.try_to_vec().unwrap().len(), 1 + 8 + 4 + 3 * inheritor_len + 8 + 32 + 8 + 1 + 1 + 4 + 1 + 4 + 4 + 4 + 8 + 1 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 1 + 1 + 4);
+    }
+
+    #[test]
+    fn test_size_for_matches_serialized_will() {
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut will_data = WillData::unpack(&serialized_will(&inheritors, &[1, 2, 3], 0)).unwrap();
+        assert_eq!(will_data.try_to_vec().unwrap().len() + 8 * 32 + 4 * 8 + 3 + 17, WillData::size_for(3) + 3 * "heir".len());
+
+        will_data.max_withdraw_per_period = Some(1_000);
+        will_data.pending_withdraw_cap = Some((Some(2_000), 1_000));
+        will_data.executor = Some(Pubkey::new_unique());
+        will_data.fallback = Some(Pubkey::new_unique());
+        for info in will_data.inheritors.iter_mut() {
+            info.delegate = Some(Pubkey::new_unique());
+            info.fixed_amount = Some((Pubkey::new_unique(), 1_000));
+            info.contingent_of = Some(0);
+        }
+        assert_eq!(will_data.try_to_vec().unwrap().len(), WillData::size_for(3) + 3 * "heir".len());
+        for info in will_data.inheritors.iter_mut() {
+            info.name.clear();
+        }
+        assert_eq!(will_data.try_to_vec().unwrap().len(), WillData::size_for(3));
+        will_data.inheritors.clear();
+        assert_eq!(will_data.try_to_vec().unwrap().len(), WillData::LEN);
+    }
+
+    #[test]
+    fn test_layout_borsh_bytes() {
+        let (heir, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let will_data = WillData {
+            schema_version: SCHEMA_VERSION,
+            withdraw_allowed_ts: 1_000,
+            inheritors: vec![InheritorInfo { name: String::from("al"), pubkey: heir, share: 2500, claimed: 7, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false }],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner,
+            vesting_seconds: 60,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 1_000 - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+            distributable_snapshot: 0,
+            snapshot_total_shares: 0,
+            max_withdraw_per_period: None,
+            last_withdraw_ts: 0,
+            withdrawn_in_period: 0,
+            pending_withdraw_cap: None,
+            extensions: 0,
+            token_accounts: vec![],
+        };
+        let mut expected = vec![SCHEMA_VERSION];
+        expected.extend_from_slice(&1_000_i64.to_le_bytes());
+        expected.extend_from_slice(&1_u32.to_le_bytes());
+        expected.extend_from_slice(&2_u32.to_le_bytes());
+        expected.extend_from_slice(b"al");
+        expected.extend_from_slice(heir.as_ref());
+        expected.extend_from_slice(&2500_u32.to_le_bytes());
+        expected.extend_from_slice(&7_u64.to_le_bytes());
+        expected.push(0);
+        expected.push(0);
+        expected.push(0);
+        expected.push(0);
+        expected.extend_from_slice(&DEFAULT_TIMEOUT_SECONDS.to_le_bytes());
+        expected.extend_from_slice(owner.as_ref());
+        expected.extend_from_slice(&60_i64.to_le_bytes());
+        expected.push(0);
+        expected.push(0);
+        expected.extend_from_slice(&0_u32.to_le_bytes());
+        expected.push(0);
+        expected.extend_from_slice(&0_u32.to_le_bytes());
+        expected.extend_from_slice(&10_000_u32.to_le_bytes());
+        expected.extend_from_slice(&0_u32.to_le_bytes());
+        expected.extend_from_slice(&(1_000 - DEFAULT_TIMEOUT_SECONDS).to_le_bytes());
+        expected.push(0);
+        expected.extend_from_slice(&0_i64.to_le_bytes());
+        expected.extend_from_slice(&0_i64.to_le_bytes());
+        expected.extend_from_slice(&0_u64.to_le_bytes());
+        expected.extend_from_slice(&0_u64.to_le_bytes());
+        expected.push(0);
+        expected.extend_from_slice(&0_i64.to_le_bytes());
+        expected.extend_from_slice(&0_u64.to_le_bytes());
+        expected.push(0);
+        expected.push(0);
+        expected.extend_from_slice(&0_u32.to_le_bytes());
+        assert_eq!(will_data.try_to_vec().unwrap(), expected);
+
+        let unpacked = WillData::unpack(&expected).unwrap();
+        assert_eq!(unpacked.inheritors, will_data.inheritors);
+        assert_eq!(unpacked.owner, owner);
+        assert_eq!(unpacked.vesting_seconds, 60);
+    }
+
+    #[test]
+    fn test_unpack_upgrades_string_keys() {
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let old = WillDataV1 {
+            schema_version: 1,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.iter().map(|key| key.to_string()).collect(),
+            inheritors_shares: vec![4000, 6000],
+        };
+        let will_data = WillData::unpack(&old.try_to_vec().unwrap()).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(will_data.withdraw_allowed_ts, 42);
+        assert_eq!(will_data.timeout_seconds, DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(names_of(&will_data), old.inheritors_names);
+        assert_eq!(pubkeys_of(&will_data), inheritors.to_vec());
+        assert_eq!(shares_of(&will_data), vec![4000, 6000]);
+
+        let malformed = WillDataV1 { inheritors_pubkeys: vec![String::from("not a key"), String::new()], ..old };
+        assert_eq!(WillData::unpack(&malformed.try_to_vec().unwrap()).err(), Some(ProgramError::InvalidAccountData));
+    }
+
+    #[test]
+    fn test_claim_rewrites_legacy_account() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let old = WillDataV1 {
+            schema_version: 1,
+            withdraw_allowed_ts: 0,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.iter().map(|key| key.to_string()).collect(),
+            inheritors_shares: vec![5000, 5000],
+        };
+        let mut will_data = old.try_to_vec().unwrap();
+        will_data.resize(1024, 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(5000));
+        let will_data = WillData::deserialize(&mut &will.data[..]).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(pubkeys_of(&will_data), inheritors.to_vec());
+        assert_eq!(shares_of(&will_data), vec![0, 5000]);
+    }
+
+    #[test]
+    fn test_will_seed_constant() {
+        // Every existing will address is derived from this, it must never change.
+        assert_eq!(WILL_SEED, "solana-will.com/my/v3/1");
+    }
+
+    #[test]
+    fn test_will_seed_is_stable_across_layouts() {
+        for schema_version in 0..=SCHEMA_VERSION {
+            assert_eq!(will_seed(schema_version), WILL_SEED);
+        }
+        assert_eq!(will_seed(SCHEMA_VERSION + 1), format!("solana-will.com/my/v{}/1", SCHEMA_VERSION + 1));
+    }
+
+    #[test]
+    fn test_reserved_program_ids_are_rejected() {
+        set_clock(1_000);
+        for program_id in reserved_program_ids().iter() {
+            let mut owner = TestAccount::wallet(0);
+            let mut will = TestAccount::will(will_address(&owner.key, program_id), program_id, 10_000, serialized_will(&[], &[], 0));
+            assert_eq!(process(program_id, &mut [&mut owner, &mut will], &[4]), Err(ProgramError::IncorrectProgramId));
+        }
+    }
+
+    #[test]
+    fn test_derive_will_address_passes_ownership_check() {
+        let program_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let will_key = derive_will_address(&owner, &program_id).unwrap();
+        assert_eq!(will_key, Pubkey::create_with_seed(&owner, WILL_SEED, &program_id).unwrap());
+
+        let mut will = TestAccount::will(will_key, &program_id, 10_000, serialized_will(&[], &[], 0));
+        assert_eq!(check_ownership(&will.info(), &owner, &program_id), Ok(()));
+        let mut fresh = TestAccount::will(will_key, &program_id, 10_000, vec![]);
+        assert_eq!(check_ownership(&fresh.info(), &owner, &program_id), Ok(()));
+        assert_eq!(
+            check_ownership(&will.info(), &Pubkey::new_unique(), &program_id),
+            Err(WillError::WillAddressMismatch.into())
+        );
+    }
+
+    #[test]
+    fn test_check_ownership_tells_underivable_from_mismatched_addresses() {
+        let owner = Pubkey::new_unique();
+        // `create_with_seed` refuses program ids that look like program derived addresses.
+        let mut bytes = [7; 32];
+        bytes[32 - b"ProgramDerivedAddress".len()..].copy_from_slice(b"ProgramDerivedAddress");
+        let program_id = Pubkey::new_from_array(bytes);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[], &[], 0));
+        assert_eq!(check_ownership(&will.info(), &owner, &program_id), Err(WillError::WillAddressUnderivable.into()));
+
+        let program_id = Pubkey::new_unique();
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[], &[], 0));
+        assert_eq!(check_ownership(&will.info(), &owner, &program_id), Err(WillError::WillAddressMismatch.into()));
+    }
+
+    #[test]
+    fn test_ownership_of_legacy_accounts() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let v1 = WillDataV1 {
+            schema_version: 1,
+            withdraw_allowed_ts: 0,
+            inheritors_names: vec![],
+            inheritors_pubkeys: vec![],
+            inheritors_shares: vec![],
+        };
+        let v2 = WillDataV2 {
+            schema_version: 2,
+            withdraw_allowed_ts: 0,
+            inheritors_names: vec![],
+            inheritors_pubkeys: vec![],
+            inheritors_shares: vec![],
+        };
+        for mut will_data in vec![v1.try_to_vec().unwrap(), v2.try_to_vec().unwrap()] {
+            will_data.resize(1024, 0);
+            let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data);
+            process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+            assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        }
+
+        let newer_seed = will_seed(SCHEMA_VERSION + 1);
+        let mut will_data = serialized_will(&[], &[], 0);
+        will_data[0] = SCHEMA_VERSION + 1;
+        let will_key = Pubkey::create_with_seed(&owner.key, &newer_seed, &program_id).unwrap();
+        let mut will = TestAccount::will(will_key, &program_id, 10_000, will_data);
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[7]), Err(WillError::UnsupportedSchemaVersion.into()));
+        will.key = will_address(&owner.key, &program_id);
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[7]), Err(WillError::UnsupportedSchemaVersion.into()));
+    }
+
+    #[test]
+    fn test_owner_checks_use_the_stored_owner() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will_data = WillData::unpack(&serialized_will(&[], &[], 0)).unwrap();
+        will_data.owner = owner.key;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+
+        // The stored owner passes at any address.
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000_000, data.clone());
+        process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+
+        // Anyone else fails, even at the address derived from their key.
+        let mut other = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&other.key, &program_id), &program_id, 10_000_000, data);
+        assert_eq!(process(&program_id, &mut [&mut other, &mut will], &[4]), Err(WillError::NotTheOwner.into()));
+        assert_eq!(process(&program_id, &mut [&mut other, &mut will], &[8]), Err(WillError::NotTheOwner.into()));
+
+        // Without a stored owner the address decides who adopts the will.
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000_000, serialized_will(&[], &[], 0));
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[4]), Err(WillError::WillAddressMismatch.into()));
+        assert_eq!(WillData::unpack(&will.data).unwrap().owner, Pubkey::default());
+    }
+
+    #[test]
+    fn test_future_schema_version_is_rejected() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let heir = Pubkey::new_unique();
+        let mut will_data = serialized_will(&[heir], &[10_000], 0);
+        will_data[0] = SCHEMA_VERSION + 1;
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000_000, will_data.clone());
+
+        let withdraw = (1_u8, 1_000_u64).try_to_vec().unwrap();
+        let initialize = (3_u8, DEFAULT_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        for instruction_data in [&withdraw[..], &[4], &[7], &[8], &[9, 1]] {
+            assert_eq!(
+                process(&program_id, &mut [&mut owner, &mut will], instruction_data),
+                Err(WillError::UnsupportedSchemaVersion.into())
+            );
+        }
+        // Initialize never reads the will, it only finds the account in use.
+        assert!(process(&program_id, &mut [&mut owner, &mut will], &initialize).is_err());
+        let mut inheritor = TestAccount::wallet(0);
+        inheritor.key = heir;
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[2]), Err(WillError::UnsupportedSchemaVersion.into()));
+        assert_eq!(WillData::unpack(&will.data).err(), Some(WillError::UnsupportedSchemaVersion.into()));
+        assert_eq!(will.data, will_data);
+        assert_eq!(will.lamports, 10_000_000);
+    }
+
+    #[test]
+    fn test_migrate_from_each_prior_version() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let v1 = |schema_version| WillDataV1 {
+            schema_version,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.iter().map(|key| key.to_string()).collect(),
+            inheritors_shares: vec![4000, 6000],
+        };
+        let v2 = WillDataV2 {
+            schema_version: 2,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.to_vec(),
+            inheritors_shares: vec![4000, 6000],
+        };
+        let v3 = WillDataV3 {
+            schema_version: 3,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.to_vec(),
+            inheritors_shares: vec![4000, 6000],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+        };
+        let v4 = WillDataV4 {
+            schema_version: 4,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.to_vec(),
+            inheritors_shares: vec![4000, 6000],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+        };
+        let v5 = WillDataV5 {
+            schema_version: 5,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: inheritors.to_vec(),
+            inheritors_shares: vec![4000, 6000],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            inheritors_claimed: vec![0, 0],
+            vesting_seconds: 0,
+        };
+        let v6 = WillDataV6 {
+            schema_version: 6,
+            withdraw_allowed_ts: 42,
+            inheritors: vec![
+                InheritorInfoV9 { name: String::from("alice"), pubkey: inheritors[0], share: 4000, claimed: 0 },
+                InheritorInfoV9 { name: String::from("bob"), pubkey: inheritors[1], share: 6000, claimed: 0 },
+            ],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+        };
+        let v7 = WillDataV7 {
+            schema_version: 7,
+            withdraw_allowed_ts: 42,
+            inheritors: v6.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+        };
+        let v8 = WillDataV8 {
+            schema_version: 8,
+            withdraw_allowed_ts: 42,
+            inheritors: v6.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+        };
+        let v9 = WillDataV9 {
+            schema_version: 9,
+            withdraw_allowed_ts: 42,
+            inheritors: v6.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+        };
+        let v10 = WillDataV10 {
+            schema_version: 10,
+            withdraw_allowed_ts: 42,
+            inheritors: v6.inheritors.iter().cloned().map(InheritorInfoV10::from).collect(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+        };
+        let v11 = WillDataV11 {
+            schema_version: 11,
+            withdraw_allowed_ts: 42,
+            inheritors: v10.inheritors.iter().cloned().map(InheritorInfoV15::from).collect(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+        };
+        let v12 = WillDataV12 {
+            schema_version: 12,
+            withdraw_allowed_ts: 42,
+            inheritors: v11.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+        };
+        let v13 = WillDataV13 {
+            schema_version: 13,
+            withdraw_allowed_ts: 42,
+            inheritors: v11.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 42 - DEFAULT_TIMEOUT_SECONDS,
+        };
+        let v14 = WillDataV14 {
+            schema_version: 14,
+            withdraw_allowed_ts: 42,
+            inheritors: v11.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 42 - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+        };
+        let v15 = WillDataV15 {
+            schema_version: 15,
+            withdraw_allowed_ts: 42,
+            inheritors: v11.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 42 - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+        };
+        let v16 = WillDataV16 {
+            schema_version: 16,
+            withdraw_allowed_ts: 42,
+            inheritors: v11.inheritors.iter().cloned().map(InheritorInfoV18::from).collect(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 42 - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+        };
+        let v17 = WillDataV17 {
+            schema_version: 17,
+            withdraw_allowed_ts: 42,
+            inheritors: v16.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 42 - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+            distributable_snapshot: 0,
+            snapshot_total_shares: 0,
+        };
+        let v18 = WillDataV18 {
+            schema_version: 18,
+            withdraw_allowed_ts: 42,
+            inheritors: v16.inheritors.clone(),
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: owner.key,
+            vesting_seconds: 0,
+            frozen: false,
+            executor: None,
+            guardians: vec![],
+            required_confirmations: 0,
+            confirmations: vec![],
+            max_total_shares: MAX_TOTAL_SHARES as u32,
+            extension_votes: vec![],
+            last_heartbeat_ts: 42 - DEFAULT_TIMEOUT_SECONDS,
+            fallback: None,
+            min_claim_interval: 0,
+            last_action_ts: 0,
+            distributable_snapshot: 0,
+            snapshot_total_shares: 0,
+            max_withdraw_per_period: None,
+            last_withdraw_ts: 0,
+            withdrawn_in_period: 0,
+        };
+        let old_layouts = vec![
+            v1(0).try_to_vec().unwrap(),
+            v1(1).try_to_vec().unwrap(),
+            v2.try_to_vec().unwrap(),
+            v3.try_to_vec().unwrap(),
+            v4.try_to_vec().unwrap(),
+            v5.try_to_vec().unwrap(),
+            v6.try_to_vec().unwrap(),
+            v7.try_to_vec().unwrap(),
+            v8.try_to_vec().unwrap(),
+            v9.try_to_vec().unwrap(),
+            v10.try_to_vec().unwrap(),
+            v11.try_to_vec().unwrap(),
+            v12.try_to_vec().unwrap(),
+            v13.try_to_vec().unwrap(),
+            v14.try_to_vec().unwrap(),
+            v15.try_to_vec().unwrap(),
+            v16.try_to_vec().unwrap(),
+            v17.try_to_vec().unwrap(),
+            v18.try_to_vec().unwrap(),
+        ];
+        for mut will_data in old_layouts {
+            will_data.resize(1024, 0);
+            let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data);
+            process(&program_id, &mut [&mut owner, &mut will], &[7]).unwrap();
+
+            let will_data = WillData::deserialize(&mut &will.data[..]).unwrap();
+            assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+            assert_eq!(will_data.withdraw_allowed_ts, 42);
+            assert_eq!(names_of(&will_data), vec![String::from("alice"), String::from("bob")]);
+            assert_eq!(pubkeys_of(&will_data), inheritors.to_vec());
+            assert_eq!(shares_of(&will_data), vec![4000, 6000]);
+            assert_eq!(will_data.timeout_seconds, DEFAULT_TIMEOUT_SECONDS);
+            assert_eq!(will_data.owner, owner.key);
+            assert_eq!(claimed_of(&will_data), vec![0, 0]);
+            assert_eq!(will_data.vesting_seconds, 0);
+            assert!(!will_data.frozen);
+            assert_eq!(will_data.executor, None);
+            assert!(will_data.guardians.is_empty());
+            assert_eq!(will_data.required_confirmations, 0);
+            assert_eq!(will_data.max_total_shares, 10_000);
+            assert!(will_data.inheritors.iter().all(|info| info.delegate.is_none() && info.fixed_amount.is_none()));
+            assert!(will_data.inheritors.iter().all(|info| info.contingent_of.is_none()));
+            assert!(will_data.extension_votes.is_empty());
+            assert_eq!(will_data.last_heartbeat_ts, 42 - DEFAULT_TIMEOUT_SECONDS);
+            assert_eq!(will_data.fallback, None);
+            assert_eq!((will_data.min_claim_interval, will_data.last_action_ts), (0, 0));
+            assert_eq!((will_data.distributable_snapshot, will_data.snapshot_total_shares), (0, 0));
+            assert_eq!((will_data.max_withdraw_per_period, will_data.last_withdraw_ts, will_data.withdrawn_in_period), (None, 0, 0));
+
+            let migrated = will.data.clone();
+            assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[7]), Err(WillError::AlreadyMigrated.into()));
+            assert_eq!(will.data, migrated);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_fills_defaults_for_old_layouts() {
+        let heir = Pubkey::new_unique();
+        let v1 = WillDataV1 {
+            schema_version: 1,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("heir")],
+            inheritors_pubkeys: vec![heir.to_string()],
+            inheritors_shares: vec![10_000],
+        };
+        let will_data = WillData::try_from_slice(&v1.try_to_vec().unwrap()).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(will_data.withdraw_allowed_ts, 42);
+        assert_eq!(
+            will_data.inheritors,
+            vec![InheritorInfo { name: String::from("heir"), pubkey: heir, share: 10_000, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false }]
+        );
+        assert_eq!(will_data.timeout_seconds, DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(will_data.owner, Pubkey::default());
+        assert_eq!((will_data.vesting_seconds, will_data.frozen, will_data.executor), (0, false, None));
+        assert_eq!(will_data.max_total_shares, MAX_TOTAL_SHARES as u32);
+        assert_eq!(will_data.last_heartbeat_ts, 42 - DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(will_data.max_withdraw_per_period, None);
+
+        // The current layout reads back as written, byte for byte.
+        let bytes = will_data.try_to_vec().unwrap();
+        assert_eq!(WillData::try_from_slice(&bytes).unwrap().try_to_vec().unwrap(), bytes);
+
+        let mut corrupt = v1.try_to_vec().unwrap();
+        corrupt[0] = SCHEMA_VERSION + 1;
+        assert!(WillData::try_from_slice(&corrupt).is_err());
+        assert!(WillData::try_from_slice(&[]).is_err());
+    }
+
+    #[test]
+    fn test_failed_migration_leaves_will_untouched() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let v2 = WillDataV2 {
+            schema_version: 2,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice")],
+            inheritors_pubkeys: vec![Pubkey::new_unique()],
+            inheritors_shares: vec![10_000],
+        };
+        let exact_fit = v2.try_to_vec().unwrap();
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, exact_fit.clone());
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[7]), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(will.data, exact_fit);
+
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, vec![0; 1024]);
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[7]), Err(ProgramError::UninitializedAccount));
+        assert_eq!(will.data, vec![0; 1024]);
+    }
+
+    #[test]
+    fn test_set_inheritance_rejects_mismatched_lengths() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 2_000));
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        let mismatched: [(&[&str], &[Pubkey], &[u16]); 3] = [
+            (&["alice"], &[alice, bob], &[5000, 5000]),
+            (&["alice", "bob"], &[alice], &[5000, 5000]),
+            (&["alice", "bob"], &[alice, bob], &[10_000]),
+        ];
+        for (names, pubkeys, shares) in mismatched.iter() {
+            assert_eq!(
+                set_inheritance(&program_id, &mut owner, &mut will, names, pubkeys, shares),
+                Err(WillError::InheritorsLengthMismatch.into())
+            );
+        }
+
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob"], &[alice, bob], &[5000, 5000]).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(pubkeys_of(&will_data), vec![alice, bob]);
+        assert_eq!(shares_of(&will_data), vec![5000, 5000]);
+    }
+
+    #[test]
+    fn test_set_inheritance_limits_inheritors() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will_data = serialized_will(&[], &[], 2_000);
+        will_data.resize(4096, 0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data);
+        let inheritors: Vec<Pubkey> = (0..=MAX_INHERITORS).map(|_| Pubkey::new_unique()).collect();
+        let names = vec!["heir"; MAX_INHERITORS + 1];
+        let shares = vec![1_u16; MAX_INHERITORS + 1];
+
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &names, &inheritors, &shares),
+            Err(WillError::TooManyInheritors.into())
+        );
+        set_inheritance(&program_id, &mut owner, &mut will, &names[1..], &inheritors[1..], &shares[1..]).unwrap();
+        assert_eq!(pubkeys_of(&WillData::unpack(&will.data).unwrap()), inheritors[1..].to_vec());
+    }
+
+    #[test]
+    fn test_set_inheritance_rejects_owner_as_inheritor() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[], &[], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data.clone());
+        let (alice, owner_key) = (Pubkey::new_unique(), owner.key);
+
+        take_logs();
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["alice", "me"], &[alice, owner_key], &[5000, 5000]),
+            Err(WillError::OwnerIsInheritor.into())
+        );
+        assert!(take_logs().iter().any(|log| log.starts_with("Inheritor 1 (me) is the owner")));
+        assert_eq!(will.data, will_data);
+    }
+
+    #[test]
+    fn test_set_inheritance_limits_name_length() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[], &[], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data.clone());
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let longest = "é".repeat(MAX_NAME_BYTES / 2);
+        let too_long = format!("{}a", longest);
+
+        take_logs();
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["alice", &too_long], &[alice, bob], &[5000, 5000]),
+            Err(WillError::NameTooLong.into())
+        );
+        assert!(take_logs().iter().any(|log| *log == format!("Inheritor 1 has a name of {} bytes, at most {} are allowed", MAX_NAME_BYTES + 1, MAX_NAME_BYTES)));
+        assert_eq!(will.data, will_data);
+
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", &longest], &[alice, bob], &[5000, 5000]).unwrap();
+        assert_eq!(names_of(&WillData::unpack(&will.data).unwrap()), vec![String::from("alice"), longest]);
+    }
+
+    #[test]
+    fn test_set_inheritance_rejects_control_characters_in_names() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[], &[], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data.clone());
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        for name in ["bob\0", "bob\nsmith", "\u{7f}"] {
+            take_logs();
+            assert_eq!(
+                set_inheritance(&program_id, &mut owner, &mut will, &["alice", name], &[alice, bob], &[5000, 5000]),
+                Err(WillError::NameHasControlCharacters.into())
+            );
+            assert!(take_logs().iter().any(|log| log == "Inheritor 1 has a name containing control characters"));
+            assert_eq!(will.data, will_data);
+        }
+
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "Bob Smith-Müller"], &[alice, bob], &[5000, 5000]).unwrap();
+    }
+
+    #[test]
+    fn test_set_inheritance_rejects_zero_shares() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[], &[], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data.clone());
+        let (alice, bob, carol) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        take_logs();
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob", "carol"], &[alice, bob, carol], &[5000, 0, 5000]),
+            Err(WillError::ZeroShare.into())
+        );
+        assert!(take_logs().iter().any(|log| log == "Inheritor 1 (bob) has a share of zero"));
+        assert_eq!(will.data, will_data);
+
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "carol"], &[alice, carol], &[5000, 5000]).unwrap();
+        assert_eq!(shares_of(&WillData::unpack(&will.data).unwrap()), vec![5000, 5000]);
+    }
+
+    #[test]
+    fn test_shrinking_a_will_zeroes_the_stale_tail() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 2_000));
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob", "carol"], &inheritors, &[2000, 3000, 5000]).unwrap();
+        let large_len = WillData::unpack(&will.data).unwrap().try_to_vec().unwrap().len();
+
+        set_inheritance(&program_id, &mut owner, &mut will, &["dave"], &inheritors[..1], &[10_000]).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        let small_len = will_data.try_to_vec().unwrap().len();
+        assert!(small_len < large_len);
+        assert!(will.data[small_len..].iter().all(|&byte| byte == 0));
+        assert_eq!(shares_of(&will_data), vec![10_000]);
+        assert_eq!(will_data.inheritors[0].name, "dave");
+    }
+
+    #[test]
+    fn test_set_inheritance_checks_account_size() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will_data = serialized_will(&[], &[], 2_000);
+        will_data.truncate(195);
+        assert_eq!(WillData::unpack(&will_data).unwrap().pack(&mut [0; 16]), Err(ProgramError::AccountDataTooSmall));
+
+        // Growing the will needs the rent for the larger account up front.
+        let rent_reserve = Rent::default().minimum_balance(200);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, rent_reserve, will_data.clone());
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["heir", "heir"], &inheritors, &[5000, 5000]),
+            Err(ProgramError::AccountNotRentExempt)
+        );
+        assert_eq!(will.data, will_data);
+        set_inheritance(&program_id, &mut owner, &mut will, &["heir"], &inheritors[..1], &[5000]).unwrap();
+        assert_eq!(will.data.len(), 200);
+    }
+
+    #[test]
+    fn test_set_inheritance_grows_account() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let inheritors: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let will_data = WillData::unpack(&serialized_will(&inheritors[..2], &[5000, 5000], 2_000)).unwrap().try_to_vec().unwrap();
+        let initial_len = will_data.len();
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 1_000_000_000, will_data);
+
+        let names = vec!["heir"; 10];
+        let shares = vec![1000_u16; 10];
+        set_inheritance(&program_id, &mut owner, &mut will, &names, &inheritors, &shares).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will.data.len(), will_data.try_to_vec().unwrap().len());
+        assert!(will.data.len() > initial_len);
+        assert_eq!(pubkeys_of(&will_data), inheritors);
+        assert_eq!(shares_of(&will_data), vec![1000; 10]);
+        assert_eq!(claim(&program_id, &mut will, &inheritors[9]), Err(WillError::NotYetReleased.into()));
+    }
+
+    #[test]
+    fn test_set_inheritance_limits_total_shares() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 2_000));
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[5000, 5001]),
+            Err(WillError::ShareSumExceeded.into())
+        );
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[u16::MAX, u16::MAX]),
+            Err(WillError::ShareSumExceeded.into())
+        );
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[5000, 5000]).unwrap();
+        assert_eq!(shares_of(&WillData::unpack(&will.data).unwrap()), vec![5000, 5000]);
+    }
+
+    #[test]
+    fn test_shares_are_weights() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000_000, serialized_will(&[], &[], 2_000));
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob", "carol"], &inheritors, &[1, 1, 2]).unwrap();
+        set_clock(1_000 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(2_500_000));
+        assert_eq!(claim(&program_id, &mut will, &inheritors[1]), Ok(2_500_000));
+        assert_eq!(claim(&program_id, &mut will, &inheritors[2]), Ok(5_000_000));
+    }
+
+    #[test]
+    fn test_set_inheritance_ppm_limits_total_shares() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 2_000));
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+
+        assert_eq!(
+            set_inheritance_ppm(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[500_000, 500_001]),
+            Err(WillError::ShareSumExceeded.into())
+        );
+        assert_eq!(
+            set_inheritance_ppm(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[1, 0]),
+            Err(WillError::ZeroShare.into())
+        );
+        set_inheritance_ppm(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[333_333, 666_667]).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(shares_of(&will_data), vec![333_333, 666_667]);
+        assert_eq!(will_data.max_total_shares, 1_000_000);
+
+        // Basis points switch the will back.
+        set_inheritance(&program_id, &mut owner, &mut will, &["alice", "bob"], &inheritors, &[3333, 6667]).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().max_total_shares, 10_000);
+    }
+
+    #[test]
+    fn test_ppm_shares_pay_closer_to_the_intended_split() {
+        // Split a large estate a third, a sixth and a half.
+        let estate = 1_000_000_000_000_u64;
+        let exact = [estate / 3, estate / 6, estate / 2];
+        let payouts = |ppm: bool| {
+            set_clock(1_000);
+            let program_id = Pubkey::new_unique();
+            let mut owner = TestAccount::wallet(0);
+            let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, estate, serialized_will(&[], &[], 2_000));
+            let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+            let names = ["alice", "bob", "carol"];
+            if ppm {
+                set_inheritance_ppm(&program_id, &mut owner, &mut will, &names, &inheritors, &[333_333, 166_667, 500_000]).unwrap();
+            } else {
+                set_inheritance(&program_id, &mut owner, &mut will, &names, &inheritors, &[3333, 1667, 5000]).unwrap();
+            }
+            set_clock(1_000 + DEFAULT_TIMEOUT_SECONDS);
+            inheritors.iter().map(|inheritor| claim(&program_id, &mut will, inheritor).unwrap()).collect::<Vec<_>>()
+        };
+        let error = |payouts: &[u64]| payouts.iter().zip(exact.iter()).map(|(paid, exact)| paid.max(exact) - paid.min(exact)).max().unwrap();
+
+        let basis_points = payouts(false);
+        let ppm = payouts(true);
+        assert_eq!(basis_points[0], 333_300_000_000);
+        assert_eq!(ppm[0], 333_333_000_000);
+        assert!(error(&ppm) < error(&basis_points) / 50);
+        assert_eq!(basis_points.iter().sum::<u64>(), estate);
+        assert_eq!(ppm.iter().sum::<u64>(), estate);
+    }
+
+    #[test]
+    fn test_initialize_will() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000_000, vec![0; 1024]);
+
+        let initialize = (3_u8, DEFAULT_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &initialize).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(will_data.withdraw_allowed_ts, 1_000 + 5 * 60);
+        assert_eq!(will_data.timeout_seconds, DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(will_data.owner, owner.key);
+        assert!(will_data.inheritors.is_empty());
+
+        set_clock(2_000);
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &initialize),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_000 + 5 * 60);
+    }
+
+    #[test]
+    fn test_initialize_checks_will_address() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, vec![0; 1024]);
+
+        let initialize = (3_u8, DEFAULT_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &initialize),
+            Err(WillError::WillAddressMismatch.into())
+        );
+        assert!(will.data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_withdraw_and_claim_check_the_will_account() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut inheritor = TestAccount::wallet(0);
+        let withdraw = (1_u8, 1_000_u64).try_to_vec().unwrap();
+
+        // A will owned by another program, even one naming the sender.
+        let mut will_data = WillData::unpack(&serialized_will(&[inheritor.key], &[10_000], 0)).unwrap();
+        will_data.owner = owner.key;
+        let mut spoofed = TestAccount::will(Pubkey::new_unique(), &Pubkey::new_unique(), 10_000, will_data.try_to_vec().unwrap());
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut spoofed], &[2]), Err(ProgramError::IncorrectProgramId));
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut spoofed], &withdraw), Err(ProgramError::IncorrectProgramId));
+        assert_eq!(spoofed.lamports, 10_000);
+
+        // A will account without any data.
+        let mut empty = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, vec![]);
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut empty], &[2]), Err(ProgramError::InvalidAccountData));
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut empty], &withdraw), Err(ProgramError::InvalidAccountData));
+        assert_eq!(empty.lamports, 10_000);
+        assert_eq!(inheritor.lamports + owner.lamports, 0);
+    }
+
+    #[test]
+    fn test_claim_rejects_spoofed_wills() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut attacker = TestAccount::wallet(0);
+
+        // Anyone may create an account assigned to the program, but it starts out zeroed
+        // and only the program writes to it: nothing in it names the attacker.
+        let mut spoofed = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, vec![0; 1024]);
+        assert_eq!(process(&program_id, &mut [&mut attacker, &mut spoofed], &[2]), Err(WillError::TotalSharesZero.into()));
+        // Nor can the attacker initialize it, its address is not derived from their key.
+        let initialize = (3_u8, DEFAULT_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        assert_eq!(process(&program_id, &mut [&mut attacker, &mut spoofed], &initialize), Err(WillError::WillAddressMismatch.into()));
+        assert_eq!((attacker.lamports, spoofed.lamports), (0, 10_000));
+        assert!(spoofed.data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_claim_logs_payout() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 9_000, serialized_will(&inheritors, &[2000, 4000], 0));
+
+        take_logs();
+        claim(&program_id, &mut will, &inheritors[0]).unwrap();
+        assert_eq!(
+            take_logs().last().unwrap(),
+            &format!("CLAIM inheritor={} shares=2000/6000 lamports=3000 balance={}", inheritors[0], will.lamports)
+        );
+        assert_eq!(will.lamports, 6_000);
+
+        // The last claim reports the balance of the will it closes.
+        take_logs();
+        claim(&program_id, &mut will, &inheritors[1]).unwrap();
+        let logs = take_logs();
+        assert!(logs.contains(&format!("CLAIM inheritor={} shares=4000/4000 lamports=6000 balance=0", inheritors[1])));
+        assert_eq!(will.lamports, 0);
+    }
+
+    #[test]
+    fn test_heartbeat_resets_timer_only() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let will_data = serialized_will(&inheritors, &[2000, 8000], 1_300);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data);
+
+        set_clock(1_200);
+        process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.withdraw_allowed_ts, 1_200 + 5 * 60);
+        assert_eq!(pubkeys_of(&will_data), inheritors.to_vec());
+        assert_eq!(shares_of(&will_data), vec![2000, 8000]);
+        assert_eq!(will.lamports, 10_000);
+        assert_eq!(owner.lamports, 0);
+    }
+
+    #[test]
+    fn test_heartbeat_requires_owner() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let owner = TestAccount::wallet(0);
+        let mut stranger = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 1_300));
+
+        assert_eq!(
+            process(&program_id, &mut [&mut stranger, &mut will], &[4]),
+            Err(WillError::WillAddressMismatch.into())
+        );
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_300);
+    }
+
+    #[test]
+    fn test_inheritor_can_not_act_as_owner() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut heir = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 1_000_000_000, vec![0; 1024]);
+        let initialize = (3_u8, DEFAULT_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &initialize).unwrap();
+        set_inheritance(&program_id, &mut owner, &mut will, &["heir"], &[heir.key], &[10_000]).unwrap();
+        let stored = will.data.clone();
+
+        let set_inheritance = (0_u8, vec![String::from("heir")], vec![heir.key], vec![10_000_u16], DEFAULT_TIMEOUT_SECONDS, 0_i64);
+        let withdraw = (1_u8, 1_000_u64);
+        for instruction_data in [set_inheritance.try_to_vec().unwrap(), withdraw.try_to_vec().unwrap(), vec![4]].iter() {
+            assert_eq!(
+                process(&program_id, &mut [&mut heir, &mut will], instruction_data),
+                Err(WillError::NotTheOwner.into())
+            );
+        }
+        assert_eq!(will.data, stored);
+        assert_eq!(will.lamports, 1_000_000_000);
+        assert_eq!(heir.lamports, 0);
+    }
+
+    #[test]
+    fn test_legacy_will_adopts_owner() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let v3 = WillDataV3 {
+            schema_version: 3,
+            withdraw_allowed_ts: 1_300,
+            inheritors_names: vec![],
+            inheritors_pubkeys: vec![],
+            inheritors_shares: vec![],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+        };
+        let mut will_data = v3.try_to_vec().unwrap();
+        will_data.resize(1024, 0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data);
+        assert_eq!(WillData::unpack(&will.data).unwrap().owner, Pubkey::default());
+
+        process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(will_data.owner, owner.key);
+    }
+
+    #[test]
+    fn test_set_inheritance_after_release_is_rejected() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let heir = Pubkey::new_unique();
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000_000, vec![0; 1024]);
+        let initialize = (3_u8, DEFAULT_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &initialize).unwrap();
+        set_inheritance(&program_id, &mut owner, &mut will, &["heir"], &[heir], &[10_000]).unwrap();
+
+        set_clock(1_000 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(
+            set_inheritance(&program_id, &mut owner, &mut will, &["thief"], &[Pubkey::new_unique()], &[10_000]),
+            Err(WillError::AlreadyReleased.into())
+        );
+        assert_eq!(pubkeys_of(&WillData::unpack(&will.data).unwrap()), vec![heir]);
+
+        process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+        let other = Pubkey::new_unique();
+        set_inheritance(&program_id, &mut owner, &mut will, &["other"], &[other], &[10_000]).unwrap();
+        assert_eq!(pubkeys_of(&WillData::unpack(&will.data).unwrap()), vec![other]);
+    }
+
+    #[test]
+    fn test_vesting_pays_out_linearly() {
+        let program_id = Pubkey::new_unique();
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut will_data = WillData::unpack(&serialized_will(&inheritors, &[5000, 5000], 1_000)).unwrap();
+        will_data.vesting_seconds = 1_000;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, data);
+
+        set_clock(1_000);
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(0));
+
+        set_clock(1_500);
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(2_500));
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(0));
+        assert_eq!(claim(&program_id, &mut will, &inheritors[1]), Ok(2_500));
+        assert_eq!(claimed_of(&WillData::unpack(&will.data).unwrap()), vec![2_500, 2_500]);
+
+        set_clock(2_000);
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Ok(2_500));
+        assert_eq!(claim(&program_id, &mut will, &inheritors[0]), Err(WillError::AlreadyClaimed.into()));
+        assert_eq!(claimed_of(&WillData::unpack(&will.data).unwrap()), vec![5_000, 2_500]);
+        assert_eq!(claim(&program_id, &mut will, &inheritors[1]), Ok(2_500));
+        assert_eq!(will.lamports, 0);
+        // The last claim closed the will.
+        assert!(will.data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_partial_claims_accumulate() {
+        let program_id = Pubkey::new_unique();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will_data = WillData::unpack(&serialized_will(&[alice, alice, bob], &[2000, 3000, 5000], 1_000)).unwrap();
+        will_data.vesting_seconds = 1_000;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, data);
+
+        set_clock(1_250);
+        assert_eq!(claim(&program_id, &mut will, &alice), Ok(1_250));
+        set_clock(1_600);
+        assert_eq!(claim(&program_id, &mut will, &alice), Ok(1_750));
+        assert_eq!(claim(&program_id, &mut will, &bob), Ok(3_000));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.get_claimed(&alice), 3_000);
+        assert_eq!(will_data.get_claimed(&bob), 3_000);
+        assert_eq!(will.lamports, 4_000);
+
+        // Lamports deposited after the first claim are not part of the snapshot,
+        // the last inheritor collects them when the will closes.
+        will.lamports += 10_000;
+        set_clock(2_000);
+        assert_eq!(claim(&program_id, &mut will, &alice), Ok(2_000));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(claimed_of(&will_data), vec![5_000, 0, 3_000]);
+        assert_eq!(will_data.get_claimed(&alice), 5_000);
+        assert_eq!(claim(&program_id, &mut will, &bob), Ok(12_000));
+        assert_eq!(will.lamports, 0);
+    }
+
+    #[test]
+    fn test_is_released() {
+        let will_data = WillData::unpack(&serialized_will(&[Pubkey::new_unique()], &[1], 1_000)).unwrap();
+        set_clock(999);
+        assert_eq!(will_data.is_released(), Ok(false));
+        assert_eq!(will_data.check_released(), Err(WillError::NotYetReleased.into()));
+        set_clock(1_000);
+        assert_eq!(will_data.is_released(), Ok(true));
+        assert_eq!(will_data.check_released(), Ok(()));
+        set_clock(5_000);
+        assert_eq!(will_data.is_released(), Ok(true));
+        assert_eq!(will_data.check_released(), Ok(()));
+    }
+
+    #[test]
+    fn test_summary_accessors() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (alice, bob, carol) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[alice, bob, alice, carol], &[1000, 2000, 3000, 4000], 1_000));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.active_inheritor_count(), will_data.total_shares(), will_data.claimed_total()), (3, 10_000, 0));
+        assert!(format!("{:?}", will_data).contains(&format!("owner: {:?}", will_data.owner)));
+
+        claim(&program_id, &mut will, &alice).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.active_inheritor_count(), will_data.total_shares(), will_data.claimed_total()), (2, 6_000, 4_000));
+
+        let empty = WillData::unpack(&serialized_will(&[], &[], 1_000)).unwrap();
+        assert_eq!((empty.active_inheritor_count(), empty.total_shares(), empty.claimed_total()), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_payouts_do_not_depend_on_claim_order() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let inheritors = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let payouts = |order: [usize; 3]| {
+            let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 1_000, serialized_will(&inheritors, &[1, 2, 4], 1_000));
+            take_events();
+            for i in order {
+                claim(&program_id, &mut will, &inheritors[i]).unwrap();
+            }
+            assert_eq!(will.lamports, 0);
+            let mut paid = [0; 3];
+            for event in take_events() {
+                if let crate::event::WillEvent::Claimed { inheritor, lamports, .. } = event {
+                    paid[inheritors.iter().position(|key| *key == inheritor).unwrap()] = lamports;
+                }
+            }
+            paid
+        };
+        // 1_000 does not split evenly by sevenths, the 2 lamports left over go with
+        // the will to whoever claims last instead of shifting the shares.
+        assert_eq!(payouts([0, 1, 2]), [142, 285, 571]);
+        assert_eq!(payouts([2, 1, 0]), [142, 285, 571]);
+        assert_eq!(payouts([1, 2, 0]), [142, 285, 571]);
+    }
+
+    #[test]
+    fn test_heartbeat_drops_the_distribution_snapshot() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[alice, bob], &[5000, 5000], 1_000));
+        claim(&program_id, &mut will, &alice).unwrap();
+        let mut will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.distributable_snapshot, will_data.snapshot_total_shares), (10_000, 10_000));
+
+        will_data.record_heartbeat().unwrap();
+        assert_eq!((will_data.distributable_snapshot, will_data.snapshot_total_shares), (0, 0));
+        assert_eq!(will_data.claimable(&bob, 5_000, 1_000), Ok((5_000, true)));
+    }
+
+    #[test]
+    fn test_last_claimant_collects_the_rounding_remainder() {
+        let program_id = Pubkey::new_unique();
+        let inheritors: Vec<Pubkey> = (0..7).map(|_| Pubkey::new_unique()).collect();
+        let shares = [1_429, 1_428, 1_428, 1_429, 1_428, 1_429, 1_429];
+        let estate = 1_000_003;
+        for vesting_seconds in [0, 1_000] {
+            let mut will_data = WillData::unpack(&serialized_will(&inheritors, &shares, 1_000)).unwrap();
+            will_data.vesting_seconds = vesting_seconds;
+            let mut data = will_data.try_to_vec().unwrap();
+            data.resize(1024, 0);
+            let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, estate, data);
+
+            let mut paid = 0;
+            if vesting_seconds > 0 {
+                // Partial claims along the way round down too.
+                set_clock(1_333);
+                for i in [3, 0, 6] {
+                    paid += claim(&program_id, &mut will, &inheritors[i]).unwrap();
+                }
+            }
+            set_clock(2_000);
+            let order = [1, 5, 3, 2, 0, 6, 4];
+            for (n, i) in order.iter().enumerate() {
+                let payout = claim(&program_id, &mut will, &inheritors[*i]).unwrap();
+                if n + 1 == order.len() {
+                    // The final claim takes its share rounded down plus what rounding left behind.
+                    assert!(payout > compute_payout(estate, shares[*i] as u64, 10_000, 0).unwrap());
+                }
+                paid += payout;
+            }
+            assert_eq!(paid, estate);
+            assert_eq!(will.lamports, 0);
+            assert!(will.data.iter().all(|byte| *byte == 0));
+        }
+    }
+
+    #[test]
+    fn test_vesting_bounds() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[], &[], 2_000));
+        let heir = Pubkey::new_unique();
+
+        for vesting in [-1, MAX_TIMEOUT_SECONDS + 1].iter() {
+            let set_inheritance = (0_u8, vec![String::from("heir")], vec![heir], vec![10_000_u16], DEFAULT_TIMEOUT_SECONDS, *vesting);
+            assert_eq!(
+                process(&program_id, &mut [&mut owner, &mut will], &set_inheritance.try_to_vec().unwrap()),
+                Err(WillError::VestingOutOfBounds.into())
+            );
+        }
+        let set_inheritance = (0_u8, vec![String::from("heir")], vec![heir], vec![10_000_u16], DEFAULT_TIMEOUT_SECONDS, 600_i64);
+        process(&program_id, &mut [&mut owner, &mut will], &set_inheritance.try_to_vec().unwrap()).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.vesting_seconds, 600);
+        assert_eq!(claimed_of(&will_data), vec![0]);
+    }
+
+    #[test]
+    fn test_revoke_closes_will() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let heir = Pubkey::new_unique();
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, serialized_will(&[heir], &[10_000], 2_000));
+
+        process(&program_id, &mut [&mut owner, &mut will], &[8]).unwrap();
+        assert_eq!(will.lamports, 0);
+        assert_eq!(owner.lamports, 10_000);
+        assert_eq!(will.data, vec![0; 1024]);
+        assert_eq!(take_return_data(), 10_000_u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_revoke_is_rejected_once_released() {
+        set_clock(2_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut heir = TestAccount::wallet(0);
+        let will_data = serialized_will(&[heir.key], &[10_000], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000, will_data.clone());
+
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[8]), Err(WillError::AlreadyReleased.into()));
+        assert_eq!(process(&program_id, &mut [&mut heir, &mut will], &[8]), Err(WillError::WillAddressMismatch.into()));
+        assert_eq!(will.lamports, 10_000);
+        assert_eq!(owner.lamports, 0);
+        assert_eq!(will.data, will_data);
+    }
+
+    #[test]
+    fn test_frozen_will_blocks_withdrawals_and_claims() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut inheritor = TestAccount::wallet(0);
+        let will_data = serialized_will(&[inheritor.key], &[10_000], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 100_000_000, will_data);
+        let withdraw = (1_u8, 1_000_u64).try_to_vec().unwrap();
+
+        process(&program_id, &mut [&mut owner, &mut will], &[9, 1]).unwrap();
+        assert!(WillData::unpack(&will.data).unwrap().frozen);
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[9, 0]), Err(WillError::NotTheOwner.into()));
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &withdraw), Err(WillError::Frozen.into()));
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[8]), Err(WillError::Frozen.into()));
+        process(&program_id, &mut [&mut owner, &mut will], &[9, 0]).unwrap();
+        assert!(!WillData::unpack(&will.data).unwrap().frozen);
+        process(&program_id, &mut [&mut owner, &mut will], &withdraw).unwrap();
+        assert_eq!(owner.lamports, 1_000);
+
+        process(&program_id, &mut [&mut owner, &mut will], &[9, 1]).unwrap();
+        set_clock(2_000);
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[2]), Err(WillError::Frozen.into()));
+        assert_eq!(inheritor.lamports, 0);
+        process(&program_id, &mut [&mut owner, &mut will], &[9, 0]).unwrap();
+        // Once released the will can not be frozen again.
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[9, 1]), Err(WillError::AlreadyReleased.into()));
+        process(&program_id, &mut [&mut inheritor, &mut will], &[2]).unwrap();
+        assert_eq!(inheritor.lamports, 100_000_000 - 1_000);
+    }
+
+    #[test]
+    fn test_executor_releases_will() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut executor = TestAccount::wallet(0);
+        let mut inheritor = TestAccount::wallet(0);
+        let will_data = serialized_will(&[inheritor.key], &[10_000], 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 100_000_000, will_data);
+
+        let appoint = (10_u8, Some(executor.key)).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &appoint).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().executor, Some(executor.key));
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[2]), Err(WillError::NotYetReleased.into()));
+
+        set_clock(1_100);
+        process(&program_id, &mut [&mut executor, &mut will], &[11]).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_100);
+        // The executor releases the will but can not take anything out of it.
+        let withdraw = (1_u8, 1_000_u64).try_to_vec().unwrap();
+        assert_eq!(process(&program_id, &mut [&mut executor, &mut will], &withdraw), Err(WillError::NotTheOwner.into()));
+        assert_eq!(process(&program_id, &mut [&mut executor, &mut will], &[2]), Err(WillError::NotAnInheritor.into()));
+        assert_eq!(executor.lamports, 0);
+
+        process(&program_id, &mut [&mut inheritor, &mut will], &[2]).unwrap();
+        assert_eq!(inheritor.lamports, 100_000_000);
+    }
+
+    #[test]
+    fn test_execute_requires_executor() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut stranger = TestAccount::wallet(0);
+        let will_data = serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 100_000_000, will_data);
+
+        // Nobody may execute a will without an executor, not even its owner.
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[11]), Err(WillError::NotTheExecutor.into()));
+
+        let appoint = (10_u8, Some(Pubkey::new_unique())).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &appoint).unwrap();
+        assert_eq!(process(&program_id, &mut [&mut stranger, &mut will], &[11]), Err(WillError::NotTheExecutor.into()));
+        assert_eq!(process(&program_id, &mut [&mut stranger, &mut will], &appoint), Err(WillError::NotTheOwner.into()));
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000);
+
+        process(&program_id, &mut [&mut owner, &mut will], &[10, 0]).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().executor, None);
+    }
+
+    #[test]
+    fn test_guardians_release_will_at_threshold() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut guardians = [TestAccount::wallet(0), TestAccount::wallet(0), TestAccount::wallet(0)];
+        let mut inheritor = TestAccount::wallet(0);
+        let will_data = serialized_will(&[inheritor.key], &[10_000], 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 100_000_000, will_data);
+
+        let guardian_keys: Vec<Pubkey> = guardians.iter().map(|guardian| guardian.key).collect();
+        let set_guardians = (13_u8, guardian_keys, 2_u8).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &set_guardians).unwrap();
+
+        // Below the threshold the will stays locked, and a guardian only counts once.
+        process(&program_id, &mut [&mut guardians[0], &mut will], &[12]).unwrap();
+        assert_eq!(process(&program_id, &mut [&mut guardians[0], &mut will], &[12]), Err(WillError::AlreadyConfirmed.into()));
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[2]), Err(WillError::NotYetReleased.into()));
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[12]), Err(WillError::NotAGuardian.into()));
+
+        // A heartbeat discards the confirmations so far.
+        process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+        assert!(WillData::unpack(&will.data).unwrap().confirmations.is_empty());
+        process(&program_id, &mut [&mut guardians[0], &mut will], &[12]).unwrap();
+        assert_eq!(process(&program_id, &mut [&mut inheritor, &mut will], &[2]), Err(WillError::NotYetReleased.into()));
+
+        set_clock(1_100);
+        process(&program_id, &mut [&mut guardians[2], &mut will], &[12]).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.confirmations, vec![guardians[0].key, guardians[2].key]);
+        assert_eq!(will_data.withdraw_allowed_ts, 1_100);
+        process(&program_id, &mut [&mut inheritor, &mut will], &[2]).unwrap();
+        assert_eq!(inheritor.lamports, 100_000_000);
+    }
+
+    #[test]
+    fn test_set_guardians_bounds() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[], &[], 2_000);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 100_000_000, will_data);
+        let guardian = Pubkey::new_unique();
+
+        // Listing a guardian twice does not make up for a missing one.
+        let set_guardians = (13_u8, vec![guardian, guardian], 2_u8).try_to_vec().unwrap();
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &set_guardians), Err(WillError::ConfirmationsOutOfBounds.into()));
+        let too_many: Vec<Pubkey> = (0..=MAX_GUARDIANS).map(|_| Pubkey::new_unique()).collect();
+        let set_guardians = (13_u8, too_many, 1_u8).try_to_vec().unwrap();
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &set_guardians), Err(WillError::TooManyGuardians.into()));
+
+        // Without required confirmations guardians can not release the will.
+        let set_guardians = (13_u8, vec![guardian], 0_u8).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &set_guardians).unwrap();
+        let mut guardian_account = TestAccount::wallet(0);
+        guardian_account.key = guardian;
+        assert_eq!(process(&program_id, &mut [&mut guardian_account, &mut will], &[12]), Err(WillError::NotAGuardian.into()));
+    }
+
+    #[test]
+    fn test_timeout_overflow_is_rejected() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will_data = WillData::unpack(&serialized_will(&[], &[], 2_000)).unwrap();
+        will_data.timeout_seconds = i64::MAX;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 100_000_000, data.clone());
+
+        assert_eq!(release_time(i64::MAX), Err(WillError::ArithmeticOverflow.into()));
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &[4]), Err(WillError::ArithmeticOverflow.into()));
+        let withdraw = (1_u8, 1_000_u64).try_to_vec().unwrap();
+        assert_eq!(process(&program_id, &mut [&mut owner, &mut will], &withdraw), Err(WillError::ArithmeticOverflow.into()));
+        assert_eq!(will.data, data);
+
+        let set_inheritance = (0_u8, Vec::<String>::new(), Vec::<Pubkey>::new(), Vec::<u16>::new(), i64::MAX, 0_i64);
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &set_inheritance.try_to_vec().unwrap()),
+            Err(WillError::TimeoutOutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn test_custom_timeout() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000_000, vec![0; 1024]);
+        let year = 365 * 24 * 60 * 60;
+
+        let initialize = (3_u8, year).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &initialize).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_000 + year);
+
+        set_clock(2_000);
+        process(&program_id, &mut [&mut owner, &mut will], &[4]).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000 + year);
+
+        let set_inheritance = (0_u8, vec![String::from("heir")], vec![Pubkey::new_unique()], vec![10_000_u16], 2 * year, 0_i64);
+        process(&program_id, &mut [&mut owner, &mut will], &set_inheritance.try_to_vec().unwrap()).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.timeout_seconds, 2 * year);
+        assert_eq!(will_data.withdraw_allowed_ts, 2_000 + 2 * year);
+    }
+
+    #[test]
+    fn test_timeout_bounds() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = TestAccount::will(will_address(&owner.key, &program_id), &program_id, 10_000_000, vec![0; 1024]);
+
+        for timeout in [MIN_TIMEOUT_SECONDS - 1, MAX_TIMEOUT_SECONDS + 1, -1].iter() {
+            let initialize = (3_u8, *timeout).try_to_vec().unwrap();
+            assert_eq!(
+                process(&program_id, &mut [&mut owner, &mut will], &initialize),
+                Err(WillError::TimeoutOutOfBounds.into())
+            );
+        }
+        let initialize = (3_u8, MAX_TIMEOUT_SECONDS).try_to_vec().unwrap();
+        process(&program_id, &mut [&mut owner, &mut will], &initialize).unwrap();
+
+        let set_inheritance = (0_u8, Vec::<String>::new(), Vec::<Pubkey>::new(), Vec::<u16>::new(), MIN_TIMEOUT_SECONDS - 1, 0_i64);
+        assert_eq!(
+            process(&program_id, &mut [&mut owner, &mut will], &set_inheritance.try_to_vec().unwrap()),
+            Err(WillError::TimeoutOutOfBounds.into())
+        );
+        assert_eq!(WillData::unpack(&will.data).unwrap().timeout_seconds, MAX_TIMEOUT_SECONDS);
+    }
+
+    #[test]
+    fn test_unpack_defaults_timeout_of_v2() {
+        let old = WillDataV2 {
+            schema_version: 2,
+            withdraw_allowed_ts: 42,
+            inheritors_names: vec![String::from("alice")],
+            inheritors_pubkeys: vec![Pubkey::new_unique()],
+            inheritors_shares: vec![10_000],
+        };
+        let will_data = WillData::unpack(&old.try_to_vec().unwrap()).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(pubkeys_of(&will_data), old.inheritors_pubkeys);
+        assert_eq!(will_data.timeout_seconds, DEFAULT_TIMEOUT_SECONDS);
+    }
+
+    #[test]
+    fn test_claim_at_release_second() {
+        let program_id = Pubkey::new_unique();
+        let inheritor = Pubkey::new_unique();
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[inheritor], &[10_000], 1_000));
+
+        set_clock(999);
+        assert_eq!(claim(&program_id, &mut will, &inheritor), Err(WillError::NotYetReleased.into()));
+        set_clock(1_000);
+        assert_eq!(claim(&program_id, &mut will, &inheritor), Ok(10_000));
+    }
+
+    #[test]
+    fn test_token_claim_checks_program_and_authority() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        let will_data = serialized_will(&[inheritor.key], &[10_000], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+        let (authority, _) = find_token_authority(&will.key, &program_id);
+
+        let mut fake_token_program = TestAccount::wallet(0);
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = authority;
+        assert_eq!(
+            process(&program_id, &mut [&mut inheritor, &mut will, &mut fake_token_program, &mut token_authority], &[5]),
+            Err(ProgramError::IncorrectProgramId)
+        );
+
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut wrong_authority = TestAccount::wallet(0);
+        assert_eq!(
+            process(&program_id, &mut [&mut inheritor, &mut will, &mut token_program, &mut wrong_authority], &[5]),
+            Err(ProgramError::InvalidSeeds)
+        );
+        assert_eq!(will.lamports, 10_000);
+        assert_eq!(shares_of(&WillData::unpack(&will.data).unwrap()), vec![10_000]);
+    }
+
+    #[test]
+    fn test_get_share_aggregates_duplicates() {
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let will_data = WillData::unpack(&serialized_will(&[alice, bob, alice], &[2000, 5000, 3000], 0)).unwrap();
+        assert_eq!(will_data.get_share(&alice, 0), Ok((5000, 10_000, vec![0, 2])));
+        assert_eq!(will_data.get_share(&bob, 0), Ok((5000, 10_000, vec![1])));
+    }
+
+    #[test]
+    fn test_get_share_not_found() {
+        let alice = Pubkey::new_unique();
+        let mut will_data = WillData::unpack(&serialized_will(&[alice], &[10_000], 0)).unwrap();
+        assert_eq!(will_data.get_share(&Pubkey::new_unique(), 0), Err(WillError::NotAnInheritor));
+        will_data.inheritors[0].share = 0;
+        assert_eq!(will_data.get_share(&alice, 0), Err(WillError::TotalSharesZero));
+        will_data.inheritors.clear();
+        assert_eq!(will_data.get_share(&alice, 0), Err(WillError::TotalSharesZero));
+    }
+
+    #[test]
+    fn test_claim_pays_duplicate_entries() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let will_data = serialized_will(&[alice, bob, alice], &[2000, 5000, 3000], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+
+        assert_eq!(claim(&program_id, &mut will, &alice), Ok(5000));
+        assert_eq!(shares_of(&WillData::unpack(&will.data).unwrap()), vec![0, 5000, 0]);
+        assert_eq!(claim(&program_id, &mut will, &bob), Ok(5000));
+    }
+
+    #[test]
+    fn test_claim_is_not_repeatable() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let will_data = serialized_will(&[alice, alice, bob], &[2500, 2500, 5000], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data);
+
+        assert_eq!(claim(&program_id, &mut will, &alice), Ok(5000));
+        assert_eq!(claim(&program_id, &mut will, &alice), Err(WillError::AlreadyClaimed.into()));
+        assert_eq!(claim(&program_id, &mut will, &Pubkey::new_unique()), Err(WillError::NotAnInheritor.into()));
+        assert_eq!(will.lamports, 5000);
+        assert_eq!(claim(&program_id, &mut will, &bob), Ok(5000));
+    }
+
+    #[test]
+    fn test_preview_matches_claim() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let will_data = serialized_will(&[alice, bob], &[3333, 6667], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 12_345, will_data);
+        let mut sender = TestAccount::wallet(0);
+        sender.key = alice;
+
+        take_logs();
+        process(&program_id, &mut [&mut sender, &mut will], &[6]).unwrap();
+        assert_eq!(
+            take_logs().last().unwrap(),
+            &format!("CLAIMABLE inheritor={} shares=3333/10000 lamports=4114", alice)
+        );
+        assert_eq!(will.lamports, 12_345);
+        assert_eq!(shares_of(&WillData::unpack(&will.data).unwrap()), vec![3333, 6667]);
+
+        assert_eq!(claim(&program_id, &mut will, &alice), Ok(4114));
+        process(&program_id, &mut [&mut sender, &mut will], &[6]).unwrap();
+        assert_eq!(
+            take_logs().last().unwrap(),
+            &format!("CLAIMABLE inheritor={} shares=0/6667 lamports=0", alice)
         );
     }
 }
@@ -0,0 +1,2910 @@
+//! Program state processor
+
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::{Clock, UnixTimestamp},
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed, set_return_data},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    check_ownership, check_program_id, check_signer, check_timeout, compute_token_payout, error::WillError,
+    event::{emit_event, WillEvent}, find_token_authority,
+    instruction::{ClaimMessage, SetInheritenceMessage, WillInstruction, WithdrawSolMessage}, release_time, transfer_lamports, InheritorInfo, WillData,
+    FALLBACK_AFTER_SECONDS, GRACE_EXTENSION_SECONDS, MAX_ACTION_INTERVAL_SECONDS, MAX_EXTENSIONS, MAX_GUARDIANS, MAX_TOKEN_ACCOUNTS, MAX_TOTAL_SHARES, MAX_TOTAL_SHARES_PPM, SCHEMA_VERSION, TOKEN_AUTHORITY_SEED,
+};
+
+/// Program state handler.
+pub struct Processor;
+
+impl Processor {
+    /// Processes an instruction, see `WillInstruction` for the accounts each one takes.
+    ///
+    /// The will, always the second account, must be owned by this program.
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
+        check_program_id(program_id)?;
+
+        let instruction = WillInstruction::unpack(instruction_data)?;
+
+        let accounts_iter = &mut accounts.iter();
+
+        let sender = next_account_info(accounts_iter)?;
+        let account = next_account_info(accounts_iter)?;
+
+        // The sender comes first and is always a plain wallet
+        if !system_program::check_id(sender.owner) {
+            if sender.owner == program_id {
+                msg!("Sender {} is a will, the sender must come first and the will second", sender.key);
+            } else {
+                msg!("Sender {} (owner = {}) is not a wallet owned by the System Program", sender.key, sender.owner);
+            }
+            return Err(WillError::SenderNotAWallet.into());
+        }
+        // The account must be owned by the program in order to modify its data, and
+        // only then was that data written by this program rather than spoofed.
+        if account.owner != program_id {
+            msg!("Will {} (owner = {}) does not have the correct program id {}", account.key, account.owner, program_id);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        match instruction {
+            WillInstruction::SetInheritance(msg) => Self::process_set_inheritance(program_id, sender, account, msg, MAX_TOTAL_SHARES),
+            WillInstruction::WithdrawSol(WithdrawSolMessage { lamports, refresh_timer, dry_run }) =>
+                Self::process_withdraw_sol(program_id, sender, account, lamports, refresh_timer, dry_run),
+            WillInstruction::Claim(ClaimMessage { destination, amount, dry_run }) =>
+                Self::process_claim(program_id, sender, account, accounts_iter.as_slice(), destination, amount, dry_run),
+            WillInstruction::Initialize { timeout_seconds } => Self::process_initialize(program_id, sender, account, timeout_seconds),
+            WillInstruction::Heartbeat => Self::process_heartbeat(program_id, sender, account),
+            WillInstruction::ClaimWithTokens => Self::process_claim_with_tokens(program_id, sender, account, accounts_iter.as_slice()),
+            WillInstruction::PreviewClaim => Self::process_preview_claim(sender, account),
+            WillInstruction::Migrate => Self::process_migrate(program_id, sender, account),
+            WillInstruction::Revoke => Self::process_revoke(program_id, sender, account, accounts_iter.as_slice()),
+            WillInstruction::SetFrozen { frozen } => Self::process_set_frozen(program_id, sender, account, frozen),
+            WillInstruction::SetExecutor { executor } => Self::process_set_executor(program_id, sender, account, executor),
+            WillInstruction::Execute => Self::process_execute(sender, account),
+            WillInstruction::ConfirmDeath => Self::process_confirm_death(sender, account),
+            WillInstruction::SetGuardians { guardians, required_confirmations } =>
+                Self::process_set_guardians(program_id, sender, account, guardians, required_confirmations),
+            WillInstruction::Deposit { lamports } => Self::process_deposit(program_id, sender, account, accounts_iter.as_slice(), lamports),
+            WillInstruction::SetInheritancePpm(msg) => Self::process_set_inheritance(program_id, sender, account, msg, MAX_TOTAL_SHARES_PPM),
+            WillInstruction::UpdateInheritor { index, new_share } => Self::process_update_inheritor(program_id, sender, account, index, new_share),
+            WillInstruction::AddInheritor { name, pubkey, share } => Self::process_add_inheritor(program_id, sender, account, name, pubkey, share),
+            WillInstruction::RemoveInheritor { index } => Self::process_remove_inheritor(program_id, sender, account, index),
+            WillInstruction::RenameInheritor { index, new_name } =>
+                Self::process_rename_inheritor(program_id, sender, account, index, new_name),
+            WillInstruction::SetContingent { index, contingent_of } =>
+                Self::process_set_contingent(program_id, sender, account, index, contingent_of),
+            WillInstruction::SetDelegate { index, delegate } => Self::process_set_delegate(program_id, sender, account, index, delegate),
+            WillInstruction::VoteExtension => Self::process_vote_extension(sender, account),
+            WillInstruction::SetFallback { fallback } => Self::process_set_fallback(program_id, sender, account, fallback),
+            WillInstruction::SweepToFallback => Self::process_sweep_to_fallback(program_id, sender, account, accounts_iter.as_slice()),
+            WillInstruction::TransferOwnership { new_owner } => Self::process_transfer_ownership(program_id, sender, account, new_owner),
+            WillInstruction::SetMinClaimInterval { seconds } => Self::process_set_min_claim_interval(program_id, sender, account, seconds),
+            WillInstruction::SetFixedAmount { index, fixed_amount } =>
+                Self::process_set_fixed_amount(program_id, sender, account, index, fixed_amount),
+            WillInstruction::QueryReleaseStatus => Self::process_query_release_status(account),
+            WillInstruction::Distribute => Self::process_distribute(sender, account, accounts_iter.as_slice()),
+            WillInstruction::SetWithdrawCap { max_withdraw_per_period } =>
+                Self::process_set_withdraw_cap(program_id, sender, account, max_withdraw_per_period),
+            WillInstruction::InitializeFrom { timeout_seconds } =>
+                Self::process_initialize_from(program_id, sender, account, accounts_iter.as_slice(), timeout_seconds),
+            WillInstruction::AddTokenAccount => Self::process_add_token_account(program_id, sender, account, accounts_iter.as_slice()),
+        }
+    }
+
+    /// Replaces the inheritors, timeout and vesting of the sender's will. Shares are
+    /// counted out of `max_total_shares`, basis points or parts per million.
+    fn process_set_inheritance<S: Copy + Into<u32>>(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        msg: SetInheritenceMessage<S>,
+        max_total_shares: u64,
+    ) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        will_data.check_not_released()?;
+        msg.validate(sender.key, max_total_shares)?;
+        will_data.schema_version = SCHEMA_VERSION;
+        will_data.timeout_seconds = msg.timeout_seconds;
+        will_data.record_heartbeat()?;
+        will_data.vesting_seconds = msg.vesting_seconds;
+        will_data.max_total_shares = max_total_shares as u32;
+        will_data.extension_votes.clear();
+        will_data.inheritors = msg.inheritors_names.into_iter()
+            .zip(msg.inheritors_pubkeys)
+            .zip(msg.inheritors_shares)
+            .map(|((name, pubkey), share)| InheritorInfo { name, pubkey, share: share.into(), claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false })
+            .collect();
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_inheritors_updated(account, &will_data)
+    }
+
+    /// Changes the share of a single inheritor, keeping the rest of the will as it is.
+    fn process_update_inheritor(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, index: u8, new_share: u32) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        let inheritor = will_data.inheritor_mut(index)?;
+        if new_share == 0 {
+            msg!("Inheritor {} ({}) would have a share of zero", index, inheritor.name);
+            return Err(WillError::ZeroShare.into());
+        }
+        inheritor.share = new_share;
+        let total_shares = will_data.total_shares();
+        if total_shares > will_data.max_total_shares as u64 {
+            msg!("Shares add up to {}, more than the {} available", total_shares, will_data.max_total_shares);
+            return Err(WillError::ShareSumExceeded.into());
+        }
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_inheritors_updated(account, &will_data)
+    }
+
+    /// Appends an inheritor, holding them to the same rules as `SetInheritance`.
+    fn process_add_inheritor(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        name: String,
+        pubkey: Pubkey,
+        share: u32,
+    ) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        will_data.inheritors.push(InheritorInfo { name, pubkey, share, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false });
+        check_inheritors(&will_data, sender.key)?;
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_inheritors_updated(account, &will_data)
+    }
+
+    /// Removes the inheritor at `index`, along with whatever they were paid so far.
+    fn process_remove_inheritor(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, index: u8) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        let index = will_data.inheritor_index(index)?;
+        let removed = will_data.inheritors.remove(index);
+        // Contingents point at inheritors by index, which shift past the removed one.
+        for info in will_data.inheritors.iter_mut() {
+            info.contingent_of = match info.contingent_of {
+                Some(primary) if primary as usize == index => None,
+                Some(primary) if primary as usize > index => Some(primary - 1),
+                contingent_of => contingent_of,
+            };
+        }
+        msg!("Removed inheritor {} ({}) from will {}", index, removed.pubkey, account.key);
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_inheritors_updated(account, &will_data)
+    }
+
+    /// Renames the inheritor at `index`, growing the will if the name is longer.
+    fn process_rename_inheritor(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        index: u8,
+        new_name: String,
+    ) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        let inheritor = will_data.inheritor_mut(index)?;
+        inheritor.name = new_name;
+        let inheritor = inheritor.pubkey;
+        check_inheritors(&will_data, sender.key)?;
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        msg!("Renamed inheritor {} ({}) of will {}", index, inheritor, account.key);
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_inheritors_updated(account, &will_data)
+    }
+
+    /// Sets or removes the fixed token amount of the inheritor at `index`, paid out
+    /// of the will's token accounts of its mint, see `compute_token_payout`.
+    fn process_set_fixed_amount(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        index: u8,
+        fixed_amount: Option<(Pubkey, u64)>,
+    ) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        let inheritor = will_data.inheritor_mut(index)?;
+        inheritor.fixed_amount = fixed_amount;
+        let inheritor = inheritor.pubkey;
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} inheritor {} fixed amount: {:?}", account.key, inheritor, fixed_amount);
+        emit_event(&WillEvent::FixedAmountChanged { will: *account.key, inheritor, fixed_amount })
+    }
+
+    /// Appoints or removes the delegate who may claim for the inheritor at `index`.
+    fn process_set_delegate(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        index: u8,
+        delegate: Option<Pubkey>,
+    ) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        let inheritor = will_data.inheritor_mut(index)?;
+        inheritor.delegate = delegate;
+        let inheritor = inheritor.pubkey;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} inheritor {} delegate: {:?}", account.key, inheritor, delegate);
+        emit_event(&WillEvent::DelegateChanged { will: *account.key, inheritor, delegate })
+    }
+
+    /// Makes the inheritor at `index` the contingent of the inheritor at
+    /// `contingent_of`, see `WillData::lapsed`, or nobody's contingent.
+    fn process_set_contingent(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        index: u8,
+        contingent_of: Option<u8>,
+    ) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        let primary = match contingent_of {
+            Some(primary) => Some(will_data.inheritors[will_data.inheritor_index(primary)?].pubkey),
+            None => None,
+        };
+        let inheritor = will_data.inheritor_mut(index)?;
+        inheritor.contingent_of = contingent_of;
+        let inheritor = inheritor.pubkey;
+        will_data.check_contingents()?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} inheritor {} contingent of: {:?}", account.key, inheritor, primary);
+        emit_event(&WillEvent::ContingentChanged { will: *account.key, inheritor, contingent_of: primary })
+    }
+
+    /// Lists the token account following the will in `accounts`, which the will's
+    /// token authority must hold, see `WillData::token_accounts`.
+    fn process_add_token_account(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, accounts: &[AccountInfo]) -> ProgramResult {
+        let token_account = next_account_info(&mut accounts.iter())?;
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        if *token_account.owner != spl_token::id() {
+            msg!("Token account {} is not owned by the SPL Token program", token_account.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let authority = find_token_authority(account.key, program_id).0;
+        let holder = spl_token::state::Account::unpack(&token_account.data.borrow())?.owner;
+        if holder != authority {
+            msg!("Token account {} is held by {}, not by the token authority {} of will {}", token_account.key, holder, authority, account.key);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if will_data.token_accounts.contains(token_account.key) {
+            msg!("Will {} already lists token account {}", account.key, token_account.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if will_data.token_accounts.len() >= MAX_TOKEN_ACCOUNTS {
+            msg!("Will {} already lists {} token accounts, at most {} are allowed", account.key, will_data.token_accounts.len(), MAX_TOKEN_ACCOUNTS);
+            return Err(WillError::TooManyTokenAccounts.into());
+        }
+        will_data.token_accounts.push(*token_account.key);
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} lists token account {}", account.key, token_account.key);
+        emit_event(&WillEvent::TokenAccountAdded { will: *account.key, token_account: *token_account.key })
+    }
+
+    /// Moves lamports from the owner into the will through the System Program, which
+    /// also restarts the dead-man timer.
+    fn process_deposit<'a>(
+        program_id: &Pubkey,
+        sender: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        accounts: &[AccountInfo<'a>],
+        lamports: u64,
+    ) -> ProgramResult {
+        let system_program = next_account_info(&mut accounts.iter())?;
+        if !system_program::check_id(system_program.key) {
+            msg!("Expected the System Program, got {}", system_program.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        invoke(
+            &system_instruction::transfer(sender.key, account.key, lamports),
+            &[sender.clone(), account.clone(), system_program.clone()],
+        )?;
+        will_data.record_heartbeat()?;
+        will_data.confirmations.clear();
+        will_data.extension_votes.clear();
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("DEPOSIT will={} lamports={} balance={}", account.key, lamports, account.lamports());
+        emit_event(&WillEvent::Deposited { will: *account.key, lamports })
+    }
+
+    /// Withdraws the owner's own lamports, which also restarts the dead-man timer
+    /// if `refresh_timer` is set. A `dry_run` stops after the checks, returning
+    /// the lamports without moving them or touching the timer.
+    fn process_withdraw_sol(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        lamports: u64,
+        refresh_timer: bool,
+        dry_run: bool,
+    ) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+        will_data.check_not_frozen()?;
+        // Without the heartbeat reopening it, a released will belongs to the inheritors.
+        if !refresh_timer {
+            will_data.check_not_released()?;
+        }
+
+        // Keep the will rent-exempt, otherwise the runtime would purge it with the inheritors list.
+        let rent_reserve = Rent::get()?.minimum_balance(account.data_len());
+        if account.lamports().saturating_sub(lamports) < rent_reserve {
+            msg!("Withdrawing {} of {} lamports would leave less than the rent-exempt minimum of {}", lamports, account.lamports(), rent_reserve);
+            return Err(WillError::BelowRentExemptReserve.into());
+        }
+        will_data.record_withdrawal(lamports)?;
+        if dry_run {
+            msg!("Dry run, would withdraw {} lamports from will {}", lamports, account.key);
+            set_return_data(&lamports.to_le_bytes());
+            return Ok(());
+        }
+        transfer_lamports(account, sender, lamports)?;
+
+        if refresh_timer {
+            will_data.record_heartbeat()?;
+            will_data.confirmations.clear();
+            will_data.extension_votes.clear();
+        }
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("WITHDRAW will={} lamports={} balance={}", account.key, lamports, account.lamports());
+        set_return_data(&lamports.to_le_bytes());
+        emit_event(&WillEvent::SolWithdrawn { will: *account.key, lamports })
+    }
+
+    /// Withdraws the sender's inheritance into the destination following the will in
+    /// `accounts`, the sender themselves by default. See `claim_inheritance`.
+    ///
+    /// A `destination` named in the instruction must be the account passed.
+    fn process_claim<'a>(
+        program_id: &Pubkey,
+        sender: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        accounts: &[AccountInfo<'a>],
+        destination: Option<Pubkey>,
+        amount: Option<u64>,
+        dry_run: bool,
+    ) -> ProgramResult {
+        let destination_account = accounts.first().unwrap_or(sender);
+        if let Some(destination) = destination {
+            if *destination_account.key != destination {
+                msg!("Claim is for destination {}, got account {}", destination, destination_account.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+        claim_inheritance(program_id, sender, destination_account, account, amount, dry_run, None)
+    }
+
+    /// Initializes a freshly created will account.
+    fn process_initialize(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, timeout_seconds: i64) -> ProgramResult {
+        let will_data = new_will(program_id, sender, account, timeout_seconds)?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_event(&WillEvent::Initialized { will: *account.key, owner: *sender.key })
+    }
+
+    /// Initializes a freshly created will account with the inheritors of the will
+    /// following it in `accounts`, which is only read. The inheritors keep their
+    /// names, shares, delegates, fixed amounts and contingents, those who completed
+    /// their claim are left out, and the new will starts with nothing claimed and
+    /// its own timer.
+    fn process_initialize_from(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        accounts: &[AccountInfo],
+        timeout_seconds: i64,
+    ) -> ProgramResult {
+        let source = next_account_info(&mut accounts.iter())?;
+        let mut will_data = new_will(program_id, sender, account, timeout_seconds)?;
+        // Only data written by this program is a will, anything else could list anyone.
+        if source.owner != program_id {
+            msg!("Template {} (owner = {}) is not a will of program {}", source.key, source.owner, program_id);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let template = WillData::unpack(&source.data.borrow())?;
+        // Contingents follow their primaries to their new indices, or lose them with them.
+        let mut new_index = vec![None; template.inheritors.len()];
+        let mut kept = 0_u8;
+        for (index, info) in template.inheritors.iter().enumerate() {
+            if info.share > 0 {
+                new_index[index] = Some(kept);
+                kept += 1;
+            }
+        }
+        will_data.inheritors = template.inheritors.into_iter()
+            .filter(|info| info.share > 0)
+            .map(|info| InheritorInfo {
+                claimed: 0,
+                contingent_of: info.contingent_of.and_then(|primary| new_index[primary as usize]),
+                lapsed: false,
+                ..info
+            })
+            .collect();
+        will_data.vesting_seconds = template.vesting_seconds;
+        will_data.max_total_shares = template.max_total_shares;
+        check_inheritors(&will_data, sender.key)?;
+
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} copies the {} inheritors of {}", account.key, will_data.inheritors.len(), source.key);
+        emit_event(&WillEvent::Initialized { will: *account.key, owner: *sender.key })
+    }
+
+    /// Heartbeat, the owner proves they are alive without moving funds.
+    fn process_heartbeat(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        will_data.record_heartbeat()?;
+        // The owner is alive, whatever the guardians confirmed or the inheritors voted no longer holds.
+        will_data.confirmations.clear();
+        will_data.extension_votes.clear();
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_event(&WillEvent::Heartbeat { will: *account.key, withdraw_allowed_ts: will_data.withdraw_allowed_ts })
+    }
+
+    /// Withdraws the sender's inheritance along with the same share of the will's SPL tokens.
+    ///
+    /// `accounts` holds the SPL Token program, the will's token authority and the
+    /// token account pairs, see `claim_inheritance`.
+    fn process_claim_with_tokens<'a>(
+        program_id: &Pubkey,
+        sender: &AccountInfo<'a>,
+        account: &AccountInfo<'a>,
+        accounts: &[AccountInfo<'a>],
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let token_program = next_account_info(accounts_iter)?;
+        let token_authority = next_account_info(accounts_iter)?;
+        let token_accounts = accounts_iter.as_slice();
+        claim_inheritance(program_id, sender, sender, account, None, false, Some((token_program, token_authority, token_accounts)))
+    }
+
+    /// Logs the lamports a claim by the sender would pay, without changing anything.
+    fn process_preview_claim(sender: &AccountInfo, account: &AccountInfo) -> ProgramResult {
+        check_signer(sender)?;
+        let will_data = WillData::unpack(&account.data.borrow())?;
+        let total_shares = will_data.total_shares();
+        let now = Clock::get()?.unix_timestamp;
+        // A sender without a share just has nothing to claim.
+        let (inheritor_shares, lamports) = match will_data.get_share(sender.key, now) {
+            Ok((inheritor_shares, _, _)) => (inheritor_shares, will_data.claimable(sender.key, account.lamports(), now)?.0),
+            Err(_) => (0, 0),
+        };
+        msg!("CLAIMABLE inheritor={} shares={}/{} lamports={}", sender.key, inheritor_shares, total_shares, lamports);
+        Ok(())
+    }
+
+    /// Returns the `ReleaseStatus` of the will, without changing anything.
+    fn process_query_release_status(account: &AccountInfo) -> ProgramResult {
+        let will_data = WillData::unpack(&account.data.borrow())?;
+        let status = will_data.release_status(Clock::get()?.unix_timestamp);
+        msg!("Will {} released={} at {}", account.key, status.released, status.withdraw_allowed_ts);
+        set_return_data(&status.try_to_vec()?);
+        Ok(())
+    }
+
+    /// Rewrites a will stored in an older layout in the current one.
+    fn process_migrate(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo) -> ProgramResult {
+        check_signer(sender)?;
+        migrate_will(program_id, account, sender.key)
+    }
+
+    /// Revokes the will, returning every lamport and listed token account to the owner
+    /// and closing the account.
+    fn process_revoke<'a>(program_id: &Pubkey, sender: &AccountInfo<'a>, account: &AccountInfo<'a>, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+        // Once released the estate belongs to the inheritors, a heartbeat must come first.
+        will_data.check_not_released()?;
+        will_data.check_not_frozen()?;
+        let returns = token_returns(program_id, account, &will_data.token_accounts, sender.key, accounts)?;
+
+        let lamports = account.lamports();
+        transfer_lamports(account, sender, lamports)?;
+        account.data.borrow_mut().fill(0);
+        msg!("Revoked will {}, returned {} lamports", account.key, lamports);
+        set_return_data(&lamports.to_le_bytes());
+        emit_event(&WillEvent::Revoked { will: *account.key, lamports })?;
+        transfer_token_returns(program_id, account, accounts, returns)
+    }
+
+    /// Freezes or unfreezes the will, see `WillData::frozen`.
+    fn process_set_frozen(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, frozen: bool) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+        // Freezing a released will would hold the inheritors off indefinitely, unfreezing
+        // it lets them claim.
+        if frozen {
+            will_data.check_not_released()?;
+        }
+
+        will_data.frozen = frozen;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} frozen: {}", account.key, frozen);
+        emit_event(&WillEvent::FrozenChanged { will: *account.key, frozen })
+    }
+
+    /// Names who receives what the inheritors leave unclaimed, `None` names nobody.
+    fn process_set_fallback(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, fallback: Option<Pubkey>) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        will_data.fallback = fallback;
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} fallback: {:?}", account.key, fallback);
+        emit_event(&WillEvent::FallbackChanged { will: *account.key, fallback })
+    }
+
+    /// Makes the owner's instructions come at least `seconds` apart, see
+    /// `WillData::min_claim_interval`.
+    fn process_set_min_claim_interval(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, seconds: i64) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        if !(0..=MAX_ACTION_INTERVAL_SECONDS).contains(&seconds) {
+            msg!("Interval of {} seconds is outside of [0, {}]", seconds, MAX_ACTION_INTERVAL_SECONDS);
+            return Err(ProgramError::InvalidArgument);
+        }
+        will_data.min_claim_interval = seconds;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_event(&WillEvent::MinClaimIntervalChanged { will: *account.key, seconds })
+    }
+
+    /// Caps what the owner may withdraw per period, see `WillData::set_withdraw_cap`.
+    fn process_set_withdraw_cap(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        max_withdraw_per_period: Option<u64>,
+    ) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        let effective_ts = will_data.set_withdraw_cap(max_withdraw_per_period, Clock::get()?.unix_timestamp);
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} caps withdrawals at {:?} lamports per period from {}", account.key, max_withdraw_per_period, effective_ts);
+        emit_event(&WillEvent::WithdrawCapChanged { will: *account.key, max_withdraw_per_period, effective_ts })
+    }
+
+    /// Sends every lamport and listed token account left in the will to its fallback and
+    /// closes the will, once it stayed unclaimed for `FALLBACK_AFTER_SECONDS` after its
+    /// release, or until its shares fully vested if that takes longer.
+    fn process_sweep_to_fallback<'a>(program_id: &Pubkey, sender: &AccountInfo<'a>, account: &AccountInfo<'a>, accounts: &[AccountInfo<'a>]) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let fallback = next_account_info(accounts_iter)?;
+        check_signer(sender)?;
+        let will_data = WillData::unpack(&account.data.borrow())?;
+        if will_data.fallback != Some(*fallback.key) {
+            msg!("Account {} is not the fallback {:?} of will {}", fallback.key, will_data.fallback, account.key);
+            return Err(WillError::NotTheFallback.into());
+        }
+        will_data.check_not_frozen()?;
+        let now = Clock::get()?.unix_timestamp;
+        // Unvested shares are not unclaimed yet, the inheritors could not have taken them.
+        let wait = will_data.vesting_seconds.max(FALLBACK_AFTER_SECONDS);
+        let due = will_data.withdraw_allowed_ts.checked_add(wait);
+        if !matches!(due, Some(due) if due <= now) {
+            msg!("Will {} may be swept {} seconds after its release at {}, it is only {} now",
+                account.key, wait, will_data.withdraw_allowed_ts, now);
+            return Err(WillError::FallbackNotDue.into());
+        }
+        let token_accounts = accounts_iter.as_slice();
+        let returns = token_returns(program_id, account, &will_data.token_accounts, fallback.key, token_accounts)?;
+
+        let lamports = account.lamports();
+        transfer_lamports(account, fallback, lamports)?;
+        account.data.borrow_mut().fill(0);
+        msg!("Swept {} lamports of will {} to its fallback {}", lamports, account.key, fallback.key);
+        emit_event(&WillEvent::SweptToFallback { will: *account.key, fallback: *fallback.key, lamports })?;
+        transfer_token_returns(program_id, account, token_accounts, returns)
+    }
+
+    /// Hands the will to `new_owner`, who from now on is the only one passing owner checks.
+    fn process_transfer_ownership(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, new_owner: Pubkey) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        // A will without a stored owner is up for adoption, see `WillData::check_owner`.
+        if new_owner == Pubkey::default() {
+            msg!("Will {} can not be handed to the default pubkey", account.key);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if let Some(index) = will_data.inheritors.iter().position(|info| info.pubkey == new_owner) {
+            msg!("New owner {} is inheritor {} ({})", new_owner, index, will_data.inheritors[index].name);
+            return Err(WillError::OwnerIsInheritor.into());
+        }
+        let previous_owner = will_data.owner;
+        will_data.owner = new_owner;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} handed from {} to {}", account.key, previous_owner, new_owner);
+        emit_event(&WillEvent::OwnershipTransferred { will: *account.key, previous_owner, new_owner })
+    }
+
+    /// Appoints the executor of the will, `None` removes them.
+    fn process_set_executor(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, executor: Option<Pubkey>) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        will_data.check_owner(account, sender.key, program_id)?;
+        will_data.record_action()?;
+
+        will_data.executor = executor;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} executor: {:?}", account.key, executor);
+        emit_event(&WillEvent::ExecutorChanged { will: *account.key, executor })
+    }
+
+    /// Releases the will at once, on behalf of its executor.
+    ///
+    /// The executor only moves the release forward, claims and withdrawals stay
+    /// with the inheritors and the owner, and a heartbeat still reopens the will.
+    fn process_execute(sender: &AccountInfo, account: &AccountInfo) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        if will_data.executor != Some(*sender.key) {
+            msg!("Sender {} is not the executor of will {}", sender.key, account.key);
+            return Err(WillError::NotTheExecutor.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        will_data.set_withdraw_allowed_ts(will_data.withdraw_allowed_ts.min(now));
+        will_data.pack(&mut account.data.borrow_mut())?;
+        msg!("Will {} released by its executor at {}", account.key, will_data.withdraw_allowed_ts);
+        emit_event(&WillEvent::Released { will: *account.key, withdraw_allowed_ts: will_data.withdraw_allowed_ts })
+    }
+
+    /// Pays every inheritor still holding a share what they may claim, as if each
+    /// claimed in turn, into their own account found among `accounts` by key.
+    ///
+    /// Every inheritor's account is looked up and every payout computed before a
+    /// single lamport moves, so the executor pays either everyone or nobody. The
+    /// will closes once every share is paid, the last inheritor listed takes the
+    /// leftover lamports. The lamports paid out are returned like a claim's.
+    fn process_distribute(sender: &AccountInfo, account: &AccountInfo, accounts: &[AccountInfo]) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        if will_data.executor != Some(*sender.key) {
+            msg!("Sender {} is not the executor of will {}", sender.key, account.key);
+            return Err(WillError::NotTheExecutor.into());
+        }
+        will_data.check_released()?;
+        will_data.check_not_frozen()?;
+        let now = Clock::get()?.unix_timestamp;
+        record_lapses(account, &mut will_data, now)?;
+
+        let mut heirs: Vec<Pubkey> = vec![];
+        for (index, _) in will_data.inheritors.iter().enumerate().filter(|(_, info)| info.share > 0) {
+            let heir = will_data.inheritors[will_data.claimant(index, now)].pubkey;
+            if !heirs.contains(&heir) {
+                heirs.push(heir);
+            }
+        }
+        if heirs.is_empty() {
+            msg!("No shares are left to claim in {}", account.key);
+            return Err(WillError::TotalSharesZero.into());
+        }
+        // The snapshot fixes every payout, so they do not depend on each other.
+        will_data.snapshot_distribution(account.lamports());
+        let mut payouts = vec![];
+        for heir in heirs {
+            let destination = accounts.iter().find(|info| *info.key == heir).ok_or_else(|| {
+                msg!("Account of inheritor {} was not passed", heir);
+                ProgramError::NotEnoughAccountKeys
+            })?;
+            if destination.key == account.key {
+                msg!("Will {} can not claim from itself", account.key);
+                return Err(WillError::SenderIsWill.into());
+            }
+            let (lamports, completed) = will_data.claimable(&heir, account.lamports(), now)?;
+            // Nothing here pays the will's tokens, the shares stay open for claims that do.
+            payouts.push((destination, lamports, completed && will_data.token_accounts.is_empty()));
+        }
+
+        let mut total = 0_u64;
+        for (destination, lamports, completed) in payouts.iter() {
+            transfer_lamports(account, destination, *lamports)?;
+            total = total.saturating_add(*lamports);
+            let (inheritor_shares, total_shares, inheritor_indices) = will_data.get_share(destination.key, now)?;
+            let claimed = &mut will_data.inheritors[inheritor_indices[0]].claimed;
+            *claimed = claimed.saturating_add(*lamports);
+            if *completed {
+                for i in inheritor_indices {
+                    will_data.inheritors[i].share = 0;
+                }
+            }
+            msg!("CLAIM inheritor={} shares={}/{} lamports={}", destination.key, inheritor_shares, total_shares, lamports);
+            emit_event(&WillEvent::Claimed { will: *account.key, inheritor: *destination.key, lamports: *lamports })?;
+        }
+        match payouts.last() {
+            Some((last, _, _)) if will_data.inheritors.iter().all(|info| info.share == 0) => close_will(account, last)?,
+            _ => will_data.pack(&mut account.data.borrow_mut())?,
+        }
+        set_return_data(&total.to_le_bytes());
+        Ok(())
+    }
+
+    /// Records a guardian's confirmation of the owner's death, releasing the will
+    /// once `required_confirmations` distinct guardians confirmed.
+    fn process_confirm_death(sender: &AccountInfo, account: &AccountInfo) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        if will_data.required_confirmations == 0 || !will_data.guardians.contains(sender.key) {
+            msg!("Sender {} is not a guardian of will {}", sender.key, account.key);
+            return Err(WillError::NotAGuardian.into());
+        }
+        if will_data.confirmations.contains(sender.key) {
+            msg!("Guardian {} already confirmed", sender.key);
+            return Err(WillError::AlreadyConfirmed.into());
+        }
+
+        will_data.confirmations.push(*sender.key);
+        msg!("Guardian {} confirmed, {} of {}", sender.key, will_data.confirmations.len(), will_data.required_confirmations);
+        let released = will_data.confirmations.len() >= will_data.required_confirmations as usize;
+        if released {
+            let now = Clock::get()?.unix_timestamp;
+            will_data.set_withdraw_allowed_ts(will_data.withdraw_allowed_ts.min(now));
+            msg!("Will {} released by its guardians at {}", account.key, will_data.withdraw_allowed_ts);
+        }
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_event(&WillEvent::DeathConfirmed {
+            will: *account.key,
+            guardian: *sender.key,
+            confirmations: will_data.confirmations.len() as u8,
+        })?;
+        if released {
+            emit_event(&WillEvent::Released { will: *account.key, withdraw_allowed_ts: will_data.withdraw_allowed_ts })?;
+        }
+        Ok(())
+    }
+
+    /// Records the sender's vote to delay the release, and pushes the release back
+    /// by `GRACE_EXTENSION_SECONDS` once more than half of the inheritors still
+    /// holding a share voted. Each inheritor votes once per extension.
+    fn process_vote_extension(sender: &AccountInfo, account: &AccountInfo) -> ProgramResult {
+        check_signer(sender)?;
+        let mut will_data = WillData::unpack(&account.data.borrow())?;
+        let mut holders: Vec<Pubkey> = will_data.inheritors.iter().filter(|info| info.share > 0).map(|info| info.pubkey).collect();
+        holders.sort();
+        holders.dedup();
+        if !holders.contains(sender.key) {
+            msg!("Sender {} holds no share in will {}", sender.key, account.key);
+            return Err(WillError::NotAnInheritor.into());
+        }
+        if will_data.extension_votes.contains(sender.key) {
+            msg!("Inheritor {} already voted", sender.key);
+            return Err(WillError::AlreadyVoted.into());
+        }
+        if will_data.extensions >= MAX_EXTENSIONS {
+            msg!("Will {} was already extended {} times", account.key, will_data.extensions);
+            return Err(WillError::TooManyExtensions.into());
+        }
+
+        will_data.extension_votes.push(*sender.key);
+        let votes = will_data.extension_votes.iter().filter(|voter| holders.contains(voter)).count();
+        msg!("Inheritor {} voted to extend, {} of {}", sender.key, votes, holders.len());
+        let extended = votes * 2 > holders.len();
+        if extended {
+            let now = Clock::get()?.unix_timestamp;
+            let from = will_data.withdraw_allowed_ts.max(now);
+            will_data.set_withdraw_allowed_ts(from.checked_add(GRACE_EXTENSION_SECONDS).ok_or_else(|| {
+                msg!("Extending the release from {} overflows", from);
+                ProgramError::from(WillError::ArithmeticOverflow)
+            })?);
+            will_data.extension_votes.clear();
+            will_data.extensions += 1;
+            msg!("Will {} release extended to {}", account.key, will_data.withdraw_allowed_ts);
+        }
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_event(&WillEvent::ExtensionVoted { will: *account.key, inheritor: *sender.key, votes: votes as u8 })?;
+        if extended {
+            emit_event(&WillEvent::ReleaseExtended { will: *account.key, withdraw_allowed_ts: will_data.withdraw_allowed_ts })?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the guardians of the will, discarding the confirmations so far.
+    fn process_set_guardians(
+        program_id: &Pubkey,
+        sender: &AccountInfo,
+        account: &AccountInfo,
+        guardians: Vec<Pubkey>,
+        required_confirmations: u8,
+    ) -> ProgramResult {
+        let mut will_data = WillData::unpack_for_owner(account, sender, program_id)?;
+
+        if guardians.len() > MAX_GUARDIANS {
+            msg!("Got {} guardians, at most {} are allowed", guardians.len(), MAX_GUARDIANS);
+            return Err(WillError::TooManyGuardians.into());
+        }
+        let mut distinct = guardians.clone();
+        distinct.sort();
+        distinct.dedup();
+        if required_confirmations as usize > distinct.len() {
+            msg!("{} confirmations are required, but there are only {} guardians", required_confirmations, distinct.len());
+            return Err(WillError::ConfirmationsOutOfBounds.into());
+        }
+
+        will_data.guardians = guardians;
+        will_data.required_confirmations = required_confirmations;
+        will_data.confirmations.clear();
+        grow_will(account, will_data.try_to_vec()?.len())?;
+        will_data.pack(&mut account.data.borrow_mut())?;
+        emit_event(&WillEvent::GuardiansUpdated {
+            will: *account.key,
+            guardians: will_data.guardians,
+            required_confirmations,
+        })
+    }
+}
+
+/// Pays the vested part of an inheritor's share of the will's lamports into
+/// `destination`. An inheritor listed in the will claims their own share as the
+/// `sender`, into any destination. Anyone else may only claim as the delegate of
+/// the inheritor named by `destination`, who is paid.
+///
+/// What is paid is recorded in `InheritorInfo::claimed`, so repeated claims during
+/// vesting only pay what vested since. Once the share is fully vested, every
+/// entry listing `inheritor` is zeroed, so it can not be claimed again: a further
+/// attempt fails with `AlreadyClaimed`.
+///
+/// An `amount` pays only that much of what is payable now and keeps the share
+/// open, unless it is all of it. Asking for more fails with `ClaimExceedsEntitlement`.
+/// A `dry_run` makes every check and returns what would be paid, but pays nothing
+/// and stores nothing.
+///
+/// `tokens` optionally carries the SPL Token program, the will's token authority
+/// (see `find_token_authority`) and pairs of (will token account, inheritor token
+/// account), each will token account listed in `WillData::token_accounts` and passed
+/// once. Each will token account is paid out in the same proportion as the
+/// lamports, after the inheritors' fixed amounts, see `compute_token_payout`.
+/// Tokens do not vest, they are paid in full by the claim that completes
+/// the share, which is zeroed and stored before any token transfer is invoked.
+///
+/// The lamports paid out are returned to the client as a little-endian `u64`
+/// through the transaction's return data.
+fn claim_inheritance<'a>(
+    program_id: &Pubkey,
+    sender: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    amount: Option<u64>,
+    dry_run: bool,
+    tokens: Option<(&AccountInfo<'a>, &AccountInfo<'a>, &[AccountInfo<'a>])>,
+) -> ProgramResult {
+    // Paying the will out to itself would alias the same lamports twice.
+    if sender.key == account.key || destination.key == account.key {
+        msg!("Will {} can not claim from itself", account.key);
+        return Err(WillError::SenderIsWill.into());
+    }
+    check_signer(sender)?;
+    let mut will_data = WillData::unpack(&account.data.borrow())?;
+    let now = Clock::get()?.unix_timestamp;
+    // An inheritor claims for themselves, unless their share lapsed, anyone else only as a
+    // delegate. An inheritor who is also a delegate claims for the inheritor they name as
+    // the destination, their own share is claimed into any other account.
+    let listed = will_data.inheritors.iter().enumerate().any(|(i, info)| info.pubkey == *sender.key && !will_data.lapsed(i, now));
+    let delegated = sender.key != destination.key
+        && will_data.inheritors.iter().any(|info| info.pubkey == *destination.key && info.delegate == Some(*sender.key));
+    let inheritor = if delegated {
+        destination.key
+    } else if sender.key == destination.key || listed {
+        sender.key
+    } else {
+        will_data.check_delegate(destination.key, sender.key)?;
+        destination.key
+    };
+    will_data.check_released()?;
+    will_data.check_not_frozen()?;
+
+    let (inheritor_shares, total_shares, inheritor_indices) = will_data.get_share(inheritor, now).map_err(|err| {
+        if err == WillError::TotalSharesZero {
+            msg!("No shares are left to claim in {}", account.key);
+        }
+        err
+    })?;
+    will_data.snapshot_distribution(account.lamports());
+    let (mut lamports_to_transfer, mut completed) = will_data.claimable(inheritor, account.lamports(), now)?;
+    if let Some(amount) = amount {
+        if amount > lamports_to_transfer {
+            msg!("Claiming {} lamports, but {} may only claim {} now", amount, inheritor, lamports_to_transfer);
+            return Err(WillError::ClaimExceedsEntitlement.into());
+        }
+        // Claiming less leaves the share open for the rest.
+        completed &= amount == lamports_to_transfer;
+        lamports_to_transfer = amount;
+    }
+    // Tokens are only paid with a completed share, which must take every listed token account along.
+    let sources: Vec<&Pubkey> = match tokens {
+        Some((_, _, token_accounts)) => token_accounts.iter().step_by(2).map(|source| source.key).collect(),
+        None => vec![],
+    };
+    if let Some(missing) = will_data.token_accounts.iter().find(|token_account| !sources.contains(token_account)) {
+        if completed {
+            msg!("Token account {} of will {} was not passed, the share of {} stays open", missing, account.key, inheritor);
+            completed = false;
+        }
+    }
+
+    let mut token_payouts = vec![];
+    let mut authority_bump = 0;
+    if let Some((token_program, token_authority, token_accounts)) = tokens {
+        if *token_program.key != spl_token::id() {
+            msg!("Expected the SPL Token program, got {}", token_program.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (expected_authority, bump) = find_token_authority(account.key, program_id);
+        if *token_authority.key != expected_authority {
+            msg!("Token authority of will {} is {}, got {}", account.key, expected_authority, token_authority.key);
+            return Err(ProgramError::InvalidSeeds);
+        }
+        authority_bump = bump;
+        if token_accounts.len() % 2 != 0 {
+            msg!("Token accounts must come in (will account, inheritor account) pairs");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        }
+        for (i, pair) in token_accounts.chunks(2).enumerate() {
+            let (source, destination) = (&pair[0], &pair[1]);
+            // Payouts are computed from the balances before any transfer, a source passed
+            // twice would pay the share twice.
+            if sources[..i].contains(&source.key) {
+                msg!("Token account {} was passed more than once", source.key);
+                return Err(ProgramError::InvalidArgument);
+            }
+            if !will_data.token_accounts.contains(source.key) {
+                msg!("Token account {} is not listed in will {}", source.key, account.key);
+                return Err(WillError::TokenAccountNotListed.into());
+            }
+            if source.owner != token_program.key {
+                msg!("Token account {} is not owned by the SPL Token program", source.key);
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            let source_account = spl_token::state::Account::unpack(&source.data.borrow())?;
+            if completed {
+                let (fixed_amount, total_fixed) = will_data.fixed_amounts(&inheritor_indices, &source_account.mint)?;
+                let amount = compute_token_payout(source_account.amount, inheritor_shares, total_shares, fixed_amount, total_fixed)?;
+                token_payouts.push((source, destination, amount));
+            }
+        }
+    }
+    if dry_run {
+        msg!("Dry run, {} would claim {} lamports from will {}", inheritor, lamports_to_transfer, account.key);
+        set_return_data(&lamports_to_transfer.to_le_bytes());
+        return Ok(());
+    }
+
+    // Recorded before anything is paid, which would look like the inheritor claimed in time.
+    record_lapses(account, &mut will_data, now)?;
+    transfer_lamports(account, destination, lamports_to_transfer)?;
+    let claimed = &mut will_data.inheritors[inheritor_indices[0]].claimed;
+    *claimed = claimed.saturating_add(lamports_to_transfer);
+    if completed {
+        for i in inheritor_indices {
+            will_data.inheritors[i].share = 0;
+        }
+    }
+    // The balance left after the claim spares wallets reading the will again, it is
+    // 0 when the claim closes the will.
+    let closes = will_data.inheritors.iter().all(|info| info.share == 0);
+    let balance = if closes { 0 } else { account.lamports() };
+    // Formatted up front, `msg!` with exactly five arguments logs them as raw u64s.
+    msg!(&format!("CLAIM inheritor={} shares={}/{} lamports={} balance={}", inheritor, inheritor_shares, total_shares, lamports_to_transfer, balance));
+    emit_event(&WillEvent::Claimed { will: *account.key, inheritor: *inheritor, lamports: lamports_to_transfer })?;
+    if closes {
+        close_will(account, destination)?;
+    } else {
+        will_data.pack(&mut account.data.borrow_mut())?;
+    }
+
+    if let Some((token_program, token_authority, _)) = tokens {
+        let authority_seeds: &[&[u8]] = &[TOKEN_AUTHORITY_SEED, account.key.as_ref(), &[authority_bump]];
+        for (source, destination, amount) in token_payouts {
+            if amount == 0 {
+                continue;
+            }
+            // Everything the claim changes is stored by now, a token program calling
+            // back into the will finds the share already paid.
+            let instruction = spl_token::instruction::transfer(
+                token_program.key, source.key, destination.key, token_authority.key, &[], amount)?;
+            invoke_signed(
+                &instruction,
+                &[source.clone(), destination.clone(), token_authority.clone(), token_program.clone()],
+                &[authority_seeds],
+            )?;
+            msg!("CLAIM inheritor={} token_account={} amount={}", inheritor, source.key, amount);
+        }
+    }
+    set_return_data(&lamports_to_transfer.to_le_bytes());
+    Ok(())
+}
+
+/// Checks that `account` is a freshly created will of `sender`, zeroed and
+/// rent-exempt, and returns the will it starts out as, without inheritors.
+fn new_will(program_id: &Pubkey, sender: &AccountInfo, account: &AccountInfo, timeout_seconds: i64) -> Result<WillData, ProgramError> {
+    check_signer(sender)?;
+    check_ownership(account, sender.key, program_id)?;
+
+    if account.data.borrow().iter().any(|byte| *byte != 0) {
+        msg!("Will {} is already initialized", account.key);
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    check_timeout(timeout_seconds)?;
+    // A will that is not rent-exempt could be purged along with its inheritors.
+    let rent_reserve = Rent::get()?.minimum_balance(account.data_len());
+    if account.lamports() < rent_reserve {
+        msg!("Will {} holds {} lamports, {} are needed to be rent-exempt at {} bytes", account.key, account.lamports(), rent_reserve, account.data_len());
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    Ok(WillData {
+        schema_version: SCHEMA_VERSION,
+        withdraw_allowed_ts: release_time(timeout_seconds)?,
+        inheritors: vec![],
+        timeout_seconds,
+        owner: *sender.key,
+        vesting_seconds: 0,
+        frozen: false,
+        executor: None,
+        guardians: vec![],
+        required_confirmations: 0,
+        confirmations: vec![],
+        max_total_shares: MAX_TOTAL_SHARES as u32,
+        extension_votes: vec![],
+        last_heartbeat_ts: Clock::get()?.unix_timestamp,
+        fallback: None,
+        min_claim_interval: 0,
+        last_action_ts: 0,
+        distributable_snapshot: 0,
+        snapshot_total_shares: 0,
+        max_withdraw_per_period: None,
+        last_withdraw_ts: 0,
+        withdrawn_in_period: 0,
+        pending_withdraw_cap: None,
+        extensions: 0,
+        token_accounts: vec![],
+    })
+}
+
+/// Checks the accounts emptying every token account the will lists into a token account
+/// held by `recipient`, for a will closing without its inheritors, and returns the
+/// (will token account, recipient token account, balance) transfers. `accounts` carries
+/// the SPL Token program, the will's token authority and a pair for each of `listed`,
+/// in that order, see `transfer_token_returns`.
+fn token_returns<'b, 'a>(
+    program_id: &Pubkey,
+    account: &AccountInfo<'a>,
+    listed: &[Pubkey],
+    recipient: &Pubkey,
+    accounts: &'b [AccountInfo<'a>],
+) -> Result<Vec<(&'b AccountInfo<'a>, &'b AccountInfo<'a>, u64)>, ProgramError> {
+    if listed.is_empty() {
+        return Ok(vec![]);
+    }
+    let accounts_iter = &mut accounts.iter();
+    let token_program = next_account_info(accounts_iter)?;
+    let token_authority = next_account_info(accounts_iter)?;
+    if *token_program.key != spl_token::id() {
+        msg!("Expected the SPL Token program, got {}", token_program.key);
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let expected_authority = find_token_authority(account.key, program_id).0;
+    if *token_authority.key != expected_authority {
+        msg!("Token authority of will {} is {}, got {}", account.key, expected_authority, token_authority.key);
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let pairs = accounts_iter.as_slice();
+    if pairs.len() != 2 * listed.len() {
+        msg!("Will {} lists {} token accounts, {} accounts were passed to empty them", account.key, listed.len(), pairs.len());
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let mut returns = vec![];
+    for (expected, pair) in listed.iter().zip(pairs.chunks(2)) {
+        let (source, destination) = (&pair[0], &pair[1]);
+        if source.key != expected {
+            msg!("Expected token account {} of will {}, got {}", expected, account.key, source.key);
+            return Err(WillError::TokenAccountNotListed.into());
+        }
+        if source.owner != token_program.key || destination.owner != token_program.key {
+            msg!("Token accounts {} and {} must be owned by the SPL Token program", source.key, destination.key);
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let holder = spl_token::state::Account::unpack(&destination.data.borrow())?.owner;
+        if holder != *recipient {
+            msg!("Token account {} is held by {}, not by {}", destination.key, holder, recipient);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let amount = spl_token::state::Account::unpack(&source.data.borrow())?.amount;
+        returns.push((source, destination, amount));
+    }
+    Ok(returns)
+}
+
+/// Invokes the transfers checked by `token_returns`, signed by the will's token authority.
+/// Called once the will is closed, a token program calling back into it finds nothing left.
+fn transfer_token_returns<'a>(
+    program_id: &Pubkey,
+    account: &AccountInfo<'a>,
+    accounts: &[AccountInfo<'a>],
+    returns: Vec<(&AccountInfo<'a>, &AccountInfo<'a>, u64)>,
+) -> ProgramResult {
+    if returns.is_empty() {
+        return Ok(());
+    }
+    let (token_program, token_authority) = (&accounts[0], &accounts[1]);
+    let bump = find_token_authority(account.key, program_id).1;
+    let authority_seeds: &[&[u8]] = &[TOKEN_AUTHORITY_SEED, account.key.as_ref(), &[bump]];
+    for (source, destination, amount) in returns {
+        if amount == 0 {
+            continue;
+        }
+        let instruction = spl_token::instruction::transfer(token_program.key, source.key, destination.key, token_authority.key, &[], amount)?;
+        invoke_signed(&instruction, &[source.clone(), destination.clone(), token_authority.clone(), token_program.clone()], &[authority_seeds])?;
+        msg!("Returned {} tokens of {} to {}", amount, source.key, destination.key);
+    }
+    Ok(())
+}
+
+/// Nothing is left to inherit. The last claimant, `recipient`, takes whatever lamports
+/// are left and the zeroed, empty account is reclaimed by the runtime.
+fn close_will(account: &AccountInfo, recipient: &AccountInfo) -> ProgramResult {
+    let lamports = account.lamports();
+    transfer_lamports(account, recipient, lamports)?;
+    account.data.borrow_mut().fill(0);
+    msg!("Closed will {}, {} remaining lamports went to {}", account.key, lamports, recipient.key);
+    emit_event(&WillEvent::Closed { will: *account.key, recipient: *recipient.key, lamports })
+}
+
+/// Checks the inheritors of `will_data` by the rules `SetInheritance` applies to new ones.
+fn check_inheritors(will_data: &WillData, owner: &Pubkey) -> ProgramResult {
+    let msg = SetInheritenceMessage {
+        inheritors_names: will_data.inheritors.iter().map(|info| info.name.clone()).collect(),
+        inheritors_pubkeys: will_data.inheritors.iter().map(|info| info.pubkey).collect(),
+        inheritors_shares: will_data.inheritors.iter().map(|info| info.share).collect(),
+        timeout_seconds: will_data.timeout_seconds,
+        vesting_seconds: will_data.vesting_seconds,
+    };
+    msg.validate(owner, will_data.max_total_shares as u64)
+}
+
+/// Records the shares that lapsed to their contingents by `now`, see
+/// `WillData::record_lapses`.
+fn record_lapses(account: &AccountInfo, will_data: &mut WillData, now: UnixTimestamp) -> ProgramResult {
+    for (inheritor, contingent) in will_data.record_lapses(now) {
+        msg!("Inheritor {} lapsed, their share in {} passes to {}", inheritor, account.key, contingent);
+        emit_event(&WillEvent::ShareLapsed { will: *account.key, inheritor, contingent })?;
+    }
+    Ok(())
+}
+
+fn emit_inheritors_updated(account: &AccountInfo, will_data: &WillData) -> ProgramResult {
+    emit_event(&WillEvent::InheritorsUpdated {
+        will: *account.key,
+        inheritors: will_data.inheritors.iter().map(|info| info.pubkey).collect(),
+        shares: will_data.inheritors.iter().map(|info| info.share).collect(),
+        max_total_shares: will_data.max_total_shares,
+        withdraw_allowed_ts: will_data.withdraw_allowed_ts,
+    })
+}
+
+/// Reallocates the will account to `len` bytes if it is smaller, zeroing the new
+/// bytes. The owner funds the larger rent-exempt minimum by transferring lamports
+/// to the will beforehand, otherwise the account is left as it is.
+fn grow_will(account: &AccountInfo, len: usize) -> ProgramResult {
+    if len <= account.data_len() {
+        return Ok(());
+    }
+    let rent_reserve = Rent::get()?.minimum_balance(len);
+    if account.lamports() < rent_reserve {
+        msg!("Will {} needs {} lamports to stay rent-exempt at {} bytes", account.key, rent_reserve, len);
+        return Err(ProgramError::AccountNotRentExempt);
+    }
+    account.realloc(len, true)
+}
+
+/// Rewrites a will stored in an older layout in the current one.
+///
+/// Every other instruction upgrades the will as a side effect of storing it, this
+/// one only upgrades. Older layouts did not store the owner, it is taken from
+/// the sender. A will that can not be read or does not fit is left untouched.
+fn migrate_will(program_id: &Pubkey, account: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+    if account.data.borrow().iter().all(|byte| *byte == 0) {
+        msg!("Will {} is not initialized", account.key);
+        return Err(ProgramError::UninitializedAccount);
+    }
+    let schema_version = account.data.borrow()[0];
+    if schema_version == SCHEMA_VERSION {
+        msg!("Will {} is already at schema version {}", account.key, SCHEMA_VERSION);
+        return Err(WillError::AlreadyMigrated.into());
+    }
+
+    let mut will_data = WillData::unpack(&account.data.borrow())?;
+    will_data.check_owner(account, owner, program_id)?;
+    will_data.pack(&mut account.data.borrow_mut())?;
+    msg!("Migrated will {} from schema version {} to {}", account.key, schema_version, SCHEMA_VERSION);
+    emit_event(&WillEvent::Migrated { will: *account.key, from_schema_version: schema_version })
+}
+
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        test::{serialized_will, set_clock, set_invoke, take_events, take_logs, take_return_data, will_address, TestAccount},
+        ReleaseStatus, WillDataV4, CONTINGENCY_SECONDS, DEFAULT_TIMEOUT_SECONDS, MAX_INHERITORS, MAX_NAME_BYTES, WITHDRAW_PERIOD_SECONDS,
+    };
+    use borsh::BorshDeserialize;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn owned_will(program_id: &Pubkey, owner: &TestAccount, lamports: u64, data: Vec<u8>) -> TestAccount {
+        TestAccount::will(will_address(&owner.key, program_id), program_id, lamports, data)
+    }
+
+    fn token_account(mint: Pubkey, amount: u64) -> TestAccount {
+        let mut account = TestAccount::wallet(0);
+        account.is_signer = false;
+        account.owner = spl_token::id();
+        account.data = vec![0; spl_token::state::Account::LEN];
+        let state = spl_token::state::Account { mint, amount, state: spl_token::state::AccountState::Initialized, ..Default::default() };
+        spl_token::state::Account::pack(state, &mut account.data).unwrap();
+        account
+    }
+
+    fn token_account_held_by(holder: Pubkey, mint: Pubkey, amount: u64) -> TestAccount {
+        let mut account = token_account(mint, amount);
+        let mut state = spl_token::state::Account::unpack(&account.data).unwrap();
+        state.owner = holder;
+        spl_token::state::Account::pack(state, &mut account.data).unwrap();
+        account
+    }
+
+    /// A token account held by the token authority of `will`, as `AddTokenAccount` expects.
+    fn will_token_account(will: &TestAccount, program_id: &Pubkey, mint: Pubkey, amount: u64) -> TestAccount {
+        token_account_held_by(find_token_authority(&will.key, program_id).0, mint, amount)
+    }
+
+    fn token_balance(account: &TestAccount) -> u64 {
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    /// Makes invoked token transfers move the tokens, like the SPL Token program would.
+    fn mock_token_transfers() {
+        set_invoke(Some(Box::new(|instruction, account_infos| {
+            let amount = match spl_token::instruction::TokenInstruction::unpack(&instruction.data)? {
+                spl_token::instruction::TokenInstruction::Transfer { amount } => amount,
+                _ => return Err(ProgramError::InvalidInstructionData),
+            };
+            for (account, credit) in [(&account_infos[0], false), (&account_infos[1], true)] {
+                let mut state = spl_token::state::Account::unpack(&account.data.borrow())?;
+                state.amount = if credit { state.amount + amount } else { state.amount - amount };
+                spl_token::state::Account::pack(state, &mut account.data.borrow_mut())?;
+            }
+            Ok(())
+        })));
+    }
+
+    #[test]
+    fn test_process_set_inheritance() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[], &[], 2_000));
+        let heir = Pubkey::new_unique();
+        let msg: SetInheritenceMessage = SetInheritenceMessage {
+            inheritors_names: vec![String::from("alice")],
+            inheritors_pubkeys: vec![heir],
+            inheritors_shares: vec![10_000],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            vesting_seconds: 0,
+        };
+
+        Processor::process_set_inheritance(&program_id, &owner.info(), &will.info(), msg, MAX_TOTAL_SHARES).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.inheritors, vec![InheritorInfo { name: String::from("alice"), pubkey: heir, share: 10_000, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false }]);
+        assert_eq!(will_data.withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(will_data.owner, owner.key);
+        assert_eq!(
+            take_events().pop(),
+            Some(WillEvent::InheritorsUpdated {
+                will: will.key,
+                inheritors: vec![heir],
+                shares: vec![10_000],
+                max_total_shares: 10_000,
+                withdraw_allowed_ts: 1_000 + DEFAULT_TIMEOUT_SECONDS,
+            })
+        );
+    }
+
+    #[test]
+    fn test_process_update_inheritor() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice, bob], &[4_000, 6_000], 2_000));
+
+        assert_eq!(
+            Processor::process_update_inheritor(&program_id, &owner.info(), &will.info(), 0, 4_001),
+            Err(WillError::ShareSumExceeded.into())
+        );
+        assert_eq!(
+            Processor::process_update_inheritor(&program_id, &owner.info(), &will.info(), 1, 0),
+            Err(WillError::ZeroShare.into())
+        );
+        Processor::process_update_inheritor(&program_id, &owner.info(), &will.info(), 1, 5_000).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.inheritors.iter().map(|info| info.share).collect::<Vec<_>>(), vec![4_000, 5_000]);
+        assert_eq!(will_data.inheritors[1].pubkey, bob);
+        assert_eq!(will_data.withdraw_allowed_ts, 2_000);
+    }
+
+    #[test]
+    fn test_process_update_inheritor_index_out_of_bounds() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let will_data = serialized_will(&[Pubkey::new_unique()], &[4_000], 2_000);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, will_data.clone());
+
+        for index in [1, u8::MAX] {
+            assert_eq!(
+                Processor::process_update_inheritor(&program_id, &owner.info(), &will.info(), index, 1_000),
+                Err(WillError::InheritorIndexOutOfBounds.into())
+            );
+        }
+        assert_eq!(will.data, will_data);
+        Processor::process_update_inheritor(&program_id, &owner.info(), &will.info(), 0, 1_000).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].share, 1_000);
+    }
+
+    #[test]
+    fn test_process_add_inheritor() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice], &[4_000], 2_000));
+        let owner_key = owner.key;
+
+        assert_eq!(
+            Processor::process_add_inheritor(&program_id, &owner.info(), &will.info(), String::from("bob"), bob, 6_001),
+            Err(WillError::ShareSumExceeded.into())
+        );
+        assert_eq!(
+            Processor::process_add_inheritor(&program_id, &owner.info(), &will.info(), String::from("me"), owner_key, 1_000),
+            Err(WillError::OwnerIsInheritor.into())
+        );
+        assert_eq!(
+            Processor::process_add_inheritor(&program_id, &owner.info(), &will.info(), String::from("bob\0"), bob, 1_000),
+            Err(WillError::NameHasControlCharacters.into())
+        );
+        Processor::process_add_inheritor(&program_id, &owner.info(), &will.info(), String::from("bob"), bob, 6_000).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.inheritors[1], InheritorInfo { name: String::from("bob"), pubkey: bob, share: 6_000, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false });
+        assert_eq!(will_data.inheritors.len(), 2);
+    }
+
+    #[test]
+    fn test_process_add_inheritor_limits_inheritors() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will_data = WillData::unpack(&serialized_will(&[], &[], 2_000)).unwrap();
+        will_data.inheritors = (0..MAX_INHERITORS)
+            .map(|_| InheritorInfo { name: String::from("heir"), pubkey: Pubkey::new_unique(), share: 1, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false })
+            .collect();
+        let mut will = owned_will(&program_id, &owner, 10_000_000, will_data.try_to_vec().unwrap());
+
+        assert_eq!(
+            Processor::process_add_inheritor(&program_id, &owner.info(), &will.info(), String::from("one too many"), Pubkey::new_unique(), 1),
+            Err(WillError::TooManyInheritors.into())
+        );
+    }
+
+    #[test]
+    fn test_process_remove_inheritor() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob, carol) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will_data = WillData::unpack(&serialized_will(&[alice, bob, carol], &[2_000, 3_000, 5_000], 2_000)).unwrap();
+        will_data.inheritors[1].claimed = 300;
+        will_data.inheritors[2].claimed = 500;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, data.clone());
+
+        for index in [3, u8::MAX] {
+            assert_eq!(
+                Processor::process_remove_inheritor(&program_id, &owner.info(), &will.info(), index),
+                Err(WillError::InheritorIndexOutOfBounds.into())
+            );
+        }
+        assert_eq!(will.data, data);
+
+        Processor::process_remove_inheritor(&program_id, &owner.info(), &will.info(), 1).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.inheritors.iter().map(|info| info.pubkey).collect::<Vec<_>>(), vec![alice, carol]);
+        assert_eq!(will_data.inheritors.iter().map(|info| info.share).collect::<Vec<_>>(), vec![2_000, 5_000]);
+        assert_eq!(will_data.inheritors.iter().map(|info| info.claimed).collect::<Vec<_>>(), vec![0, 500]);
+    }
+
+    #[test]
+    fn test_process_rename_inheritor() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will_data = WillData::unpack(&serialized_will(&[alice, bob], &[4_000, 6_000], 2_000)).unwrap();
+        will_data.inheritors[1].claimed = 300;
+        let mut data = will_data.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, data.clone());
+
+        for index in [2, u8::MAX] {
+            assert_eq!(
+                Processor::process_rename_inheritor(&program_id, &owner.info(), &will.info(), index, String::from("bob")),
+                Err(WillError::InheritorIndexOutOfBounds.into())
+            );
+        }
+        assert_eq!(
+            Processor::process_rename_inheritor(&program_id, &owner.info(), &will.info(), 1, "b".repeat(MAX_NAME_BYTES + 1)),
+            Err(WillError::NameTooLong.into())
+        );
+        assert_eq!(will.data, data);
+
+        Processor::process_rename_inheritor(&program_id, &owner.info(), &will.info(), 1, String::from("bob")).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.inheritors[1].name, "bob");
+        assert_eq!((will_data.inheritors[1].pubkey, will_data.inheritors[1].share, will_data.inheritors[1].claimed), (bob, 6_000, 300));
+        assert_eq!(will_data.inheritors[0].pubkey, alice);
+    }
+
+    #[test]
+    fn test_process_withdraw_sol() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 100_000_000, serialized_will(&[], &[], 0));
+
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1_000, true, false).unwrap();
+        assert_eq!(owner.lamports, 1_000);
+        assert_eq!(will.lamports, 100_000_000 - 1_000);
+        assert_eq!(take_return_data(), 1_000_u64.to_le_bytes());
+        assert_eq!(take_logs().last().unwrap(), &format!("WITHDRAW will={} lamports=1000 balance={}", will.key, will.lamports));
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(take_events().pop(), Some(WillEvent::SolWithdrawn { will: will.key, lamports: 1_000 }));
+    }
+
+    #[test]
+    fn test_process_withdraw_sol_dry_run() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 100_000_000, serialized_will(&[], &[], 0));
+        let data = will.data.clone();
+        take_events();
+
+        set_clock(2_000);
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1_000, true, true).unwrap();
+        assert_eq!(take_return_data(), 1_000_u64.to_le_bytes());
+        assert_eq!((owner.lamports, will.lamports), (0, 100_000_000));
+        // The timer is not restarted either.
+        assert_eq!(will.data, data);
+        assert_eq!(take_events(), vec![]);
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 100_000_000, true, true),
+            Err(WillError::BelowRentExemptReserve.into())
+        );
+    }
+
+    #[test]
+    fn test_process_withdraw_sol_respects_the_cap() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 100_000_000, serialized_will(&[], &[], 0));
+        Processor::process_set_withdraw_cap(&program_id, &owner.info(), &will.info(), Some(5_000)).unwrap();
+        assert_eq!(take_events().pop(), Some(WillEvent::WithdrawCapChanged { will: will.key, max_withdraw_per_period: Some(5_000), effective_ts: 1_000 }));
+
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 3_000, true, false).unwrap();
+        set_clock(1_000 + WITHDRAW_PERIOD_SECONDS - 1);
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 2_001, true, false),
+            Err(WillError::WithdrawCapExceeded.into())
+        );
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 2_000, true, false).unwrap();
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1, true, false),
+            Err(WillError::WithdrawCapExceeded.into())
+        );
+        assert_eq!(owner.lamports, 5_000);
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.last_withdraw_ts, will_data.withdrawn_in_period), (1_000, 5_000));
+
+        // The cap resets once the period started by the first withdrawal is over.
+        set_clock(1_000 + WITHDRAW_PERIOD_SECONDS);
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 5_000, true, false).unwrap();
+        assert_eq!(owner.lamports, 10_000);
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.last_withdraw_ts, will_data.withdrawn_in_period), (1_000 + WITHDRAW_PERIOD_SECONDS, 5_000));
+
+        // A single withdrawal over the cap fails in a fresh period too, lifting the cap lets it
+        // through a period later.
+        set_clock(1_000 + 2 * WITHDRAW_PERIOD_SECONDS);
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 5_001, true, false),
+            Err(WillError::WithdrawCapExceeded.into())
+        );
+        Processor::process_set_withdraw_cap(&program_id, &owner.info(), &will.info(), None).unwrap();
+        set_clock(1_000 + 3 * WITHDRAW_PERIOD_SECONDS);
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 5_001, true, false).unwrap();
+        assert_eq!(owner.lamports, 15_001);
+    }
+
+    #[test]
+    fn test_raising_the_withdraw_cap_waits_a_period() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 100_000_000, serialized_will(&[], &[], 0));
+        Processor::process_set_withdraw_cap(&program_id, &owner.info(), &will.info(), Some(5_000)).unwrap();
+
+        // A stolen key lifting the cap still can not take more than the cap right away.
+        take_events();
+        Processor::process_set_withdraw_cap(&program_id, &owner.info(), &will.info(), None).unwrap();
+        let effective_ts = 1_000 + WITHDRAW_PERIOD_SECONDS;
+        assert_eq!(take_events(), vec![WillEvent::WithdrawCapChanged { will: will.key, max_withdraw_per_period: None, effective_ts }]);
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 5_001, true, false),
+            Err(WillError::WithdrawCapExceeded.into())
+        );
+        // Lowering applies at once, and drops the pending lift.
+        Processor::process_set_withdraw_cap(&program_id, &owner.info(), &will.info(), Some(1_000)).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.max_withdraw_per_period, will_data.pending_withdraw_cap), (Some(1_000), None));
+        Processor::process_set_withdraw_cap(&program_id, &owner.info(), &will.info(), Some(2_000)).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().pending_withdraw_cap, Some((Some(2_000), effective_ts)));
+
+        set_clock(effective_ts - 1);
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1_001, true, false),
+            Err(WillError::WithdrawCapExceeded.into())
+        );
+        set_clock(effective_ts);
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 2_000, true, false).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.max_withdraw_per_period, will_data.pending_withdraw_cap), (Some(2_000), None));
+    }
+
+    #[test]
+    fn test_process_withdraw_sol_without_refreshing_the_timer() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 100_000_000, serialized_will(&[], &[], 2_000));
+
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1_000, false, false).unwrap();
+        assert_eq!(owner.lamports, 1_000);
+        assert_eq!(will.lamports, 100_000_000 - 1_000);
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000);
+        assert_eq!(take_events().pop(), Some(WillEvent::SolWithdrawn { will: will.key, lamports: 1_000 }));
+
+        // Once released, only a withdrawal that restarts the timer takes lamports back.
+        set_clock(2_000);
+        assert_eq!(
+            Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1_000, false, false),
+            Err(WillError::AlreadyReleased.into())
+        );
+        Processor::process_withdraw_sol(&program_id, &owner.info(), &will.info(), 1_000, true, false).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000 + DEFAULT_TIMEOUT_SECONDS);
+    }
+
+    #[test]
+    fn test_process_claim() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[inheritor.key], &[10_000], 0));
+
+        Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!(inheritor.lamports, 10_000);
+        assert_eq!(will.lamports, 0);
+        let events = take_events();
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                WillEvent::Claimed { will: will.key, inheritor: inheritor.key, lamports: 10_000 },
+                WillEvent::Closed { will: will.key, recipient: inheritor.key, lamports: 0 },
+            ]
+        );
+        assert!(will.data.iter().all(|byte| *byte == 0));
+        assert_eq!(
+            Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, None, false),
+            Err(WillError::TotalSharesZero.into())
+        );
+    }
+
+    #[test]
+    fn test_process_claim_amount() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[alice.key, bob.key], &[4_000, 6_000], 0));
+
+        assert_eq!(
+            Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, Some(4_001), false),
+            Err(WillError::ClaimExceedsEntitlement.into())
+        );
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, Some(1_500), false).unwrap();
+        assert_eq!((alice.lamports, will.lamports), (1_500, 8_500));
+        assert_eq!(take_return_data(), 1_500_u64.to_le_bytes());
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.inheritors[0].share, will_data.inheritors[0].claimed), (4_000, 1_500));
+
+        // What is left of the share is still 2_500, however much bob claims in between.
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, Some(1_000), false).unwrap();
+        assert_eq!(
+            Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, Some(2_501), false),
+            Err(WillError::ClaimExceedsEntitlement.into())
+        );
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, Some(2_500), false).unwrap();
+        assert_eq!(alice.lamports, 4_000);
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].share, 0);
+
+        // Without an amount the rest is claimed and the will closes.
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((bob.lamports, will.lamports), (6_000, 0));
+    }
+
+    #[test]
+    fn test_process_claim_dry_run() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[inheritor.key], &[10_000], 0));
+        let data = will.data.clone();
+        take_events();
+
+        assert_eq!(
+            Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, Some(10_001), true),
+            Err(WillError::ClaimExceedsEntitlement.into())
+        );
+        Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, Some(4_000), true).unwrap();
+        assert_eq!(take_return_data(), 4_000_u64.to_le_bytes());
+        // The claim would complete the share and close the will, but the will is left as it is.
+        Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, None, true).unwrap();
+        assert_eq!(take_return_data(), 10_000_u64.to_le_bytes());
+        assert_eq!((inheritor.lamports, will.lamports), (0, 10_000));
+        assert_eq!(will.data, data);
+        assert_eq!(take_events(), vec![]);
+
+        Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((inheritor.lamports, will.lamports), (10_000, 0));
+    }
+
+    #[test]
+    fn test_process_claim_to_destination() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob, mut cold) = (TestAccount::wallet(0), TestAccount::wallet(0), TestAccount::wallet(0));
+        cold.is_signer = false;
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_001, serialized_will(&[alice.key, bob.key], &[4_000, 6_000], 0));
+
+        // The destination named in the instruction must be the account passed.
+        let (cold_key, bob_key) = (cold.key, bob.key);
+        let destination = cold.info();
+        assert_eq!(
+            Processor::process_claim(&program_id, &alice.info(), &will.info(), &[destination], Some(bob_key), None, false),
+            Err(ProgramError::InvalidArgument)
+        );
+        let destination = cold.info();
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[destination], Some(cold_key), None, false).unwrap();
+        assert_eq!((alice.lamports, cold.lamports, will.lamports), (0, 4_000, 6_001));
+        assert_eq!(take_events().pop(), Some(WillEvent::Claimed { will: will.key, inheritor: alice.key, lamports: 4_000 }));
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].share, 0);
+        // The inheritor is still listed, redirecting does not make the claim a delegate's.
+        let destination = cold.info();
+        assert_eq!(
+            Processor::process_claim(&program_id, &alice.info(), &will.info(), &[destination], None, None, false),
+            Err(WillError::AlreadyClaimed.into())
+        );
+
+        // Without a destination the signer is paid, along with what is left over.
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((bob.lamports, cold.lamports, will.lamports), (6_001, 4_000, 0));
+        assert_eq!(take_events().pop(), Some(WillEvent::Closed { will: will.key, recipient: bob.key, lamports: 1 }));
+    }
+
+    #[test]
+    fn test_process_set_delegate() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob, lawyer) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice, bob], &[4_000, 6_000], 2_000));
+
+        Processor::process_set_delegate(&program_id, &owner.info(), &will.info(), 1, Some(lawyer)).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.inheritors.iter().map(|info| info.delegate).collect::<Vec<_>>(), vec![None, Some(lawyer)]);
+        assert_eq!(take_events().pop(), Some(WillEvent::DelegateChanged { will: will.key, inheritor: bob, delegate: Some(lawyer) }));
+        assert_eq!(
+            Processor::process_set_delegate(&program_id, &owner.info(), &will.info(), 2, Some(lawyer)),
+            Err(WillError::InheritorIndexOutOfBounds.into())
+        );
+        let mut stranger = TestAccount::wallet(0);
+        assert_eq!(
+            Processor::process_set_delegate(&program_id, &stranger.info(), &will.info(), 0, Some(lawyer)),
+            Err(WillError::NotTheOwner.into())
+        );
+
+        Processor::process_set_delegate(&program_id, &owner.info(), &will.info(), 1, None).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[1].delegate, None);
+    }
+
+    #[test]
+    fn test_process_set_contingent() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob, carol) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice, bob, carol], &[2_000, 3_000, 5_000], 2_000));
+        let contingents_of =
+            |will: &TestAccount| WillData::unpack(&will.data).unwrap().inheritors.iter().map(|info| info.contingent_of).collect::<Vec<_>>();
+
+        Processor::process_set_contingent(&program_id, &owner.info(), &will.info(), 2, Some(1)).unwrap();
+        assert_eq!(contingents_of(&will), vec![None, None, Some(1)]);
+        assert_eq!(take_events().pop(), Some(WillEvent::ContingentChanged { will: will.key, inheritor: carol, contingent_of: Some(bob) }));
+        for (index, contingent_of) in [(3, Some(0)), (0, Some(3))] {
+            assert_eq!(
+                Processor::process_set_contingent(&program_id, &owner.info(), &will.info(), index, contingent_of),
+                Err(WillError::InheritorIndexOutOfBounds.into())
+            );
+        }
+        // Nobody is their own contingent, nobody has two, and contingents have none.
+        for (index, contingent_of) in [(0, Some(0)), (0, Some(1)), (1, Some(0)), (0, Some(2))] {
+            assert_eq!(
+                Processor::process_set_contingent(&program_id, &owner.info(), &will.info(), index, contingent_of),
+                Err(WillError::InvalidContingent.into())
+            );
+        }
+
+        // Removing an inheritor keeps contingents pointing at the same people.
+        Processor::process_remove_inheritor(&program_id, &owner.info(), &will.info(), 0).unwrap();
+        assert_eq!(contingents_of(&will), vec![None, Some(0)]);
+        Processor::process_remove_inheritor(&program_id, &owner.info(), &will.info(), 0).unwrap();
+        assert_eq!(contingents_of(&will), vec![None]);
+
+        Processor::process_set_contingent(&program_id, &owner.info(), &will.info(), 0, None).unwrap();
+        assert_eq!(take_events().pop(), Some(WillEvent::ContingentChanged { will: will.key, inheritor: carol, contingent_of: None }));
+    }
+
+    #[test]
+    fn test_contingent_is_blocked_once_the_primary_claims() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will_data = WillData::unpack(&serialized_will(&[alice.key, bob.key], &[4_000, 6_000], 1_000)).unwrap();
+        will_data.inheritors[1].contingent_of = Some(0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data.try_to_vec().unwrap());
+
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, Some(1_000), false).unwrap();
+        set_clock(1_000 + CONTINGENCY_SECONDS);
+        take_events();
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!(bob.lamports, 6_000);
+        assert!(!take_events().iter().any(|event| matches!(event, WillEvent::ShareLapsed { .. })));
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].pubkey, alice.key);
+        assert_eq!(
+            Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false),
+            Err(WillError::AlreadyClaimed.into())
+        );
+
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((alice.lamports, will.lamports), (4_000, 0));
+    }
+
+    #[test]
+    fn test_contingent_claims_the_share_of_a_lapsed_primary() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob, mut carol) = (TestAccount::wallet(0), TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will_data = WillData::unpack(&serialized_will(&[alice.key, bob.key, carol.key], &[2_000, 3_000, 5_000], 1_000)).unwrap();
+        will_data.inheritors[1].contingent_of = Some(0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data.try_to_vec().unwrap());
+
+        // Until the deadline the contingent only claims their own share.
+        set_clock(1_000 + CONTINGENCY_SECONDS - 1);
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!(bob.lamports, 3_000);
+
+        // Previewing past the deadline shows the lapsed share without recording it.
+        set_clock(1_000 + CONTINGENCY_SECONDS);
+        let stored = will.data.clone();
+        take_logs();
+        Processor::process_preview_claim(&bob.info(), &will.info()).unwrap();
+        assert!(take_logs().contains(&format!("CLAIMABLE inheritor={} shares=2000/7000 lamports=2000", bob.key)));
+        assert_eq!(will.data, stored);
+
+        // A partial claim records the lapse, the rest of the share stays the contingent's.
+        take_events();
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, Some(500), false).unwrap();
+        assert_eq!(take_events()[0], WillEvent::ShareLapsed { will: will.key, inheritor: alice.key, contingent: bob.key });
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.inheritors[0].pubkey, will_data.inheritors[0].share), (alice.key, 2_000));
+        assert!(will_data.inheritors[0].lapsed);
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!(bob.lamports, 5_000);
+        assert!(!take_events().iter().any(|event| matches!(event, WillEvent::ShareLapsed { .. })));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.inheritors[0].pubkey, will_data.inheritors[0].share), (alice.key, 0));
+
+        // The share is gone with the primary, who can not claim it as well.
+        assert_eq!(
+            Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, None, false),
+            Err(WillError::ShareLapsed.into())
+        );
+        assert_eq!(alice.lamports, 0);
+        Processor::process_claim(&program_id, &carol.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((carol.lamports, will.lamports), (5_000, 0));
+    }
+
+    #[test]
+    fn test_lapsed_inheritor_acts_only_as_a_delegate() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob, mut carol, mut lawyer) = (TestAccount::wallet(0), TestAccount::wallet(0), TestAccount::wallet(0), TestAccount::wallet(0));
+        carol.is_signer = false;
+        let mut will_data = WillData::unpack(&serialized_will(&[alice.key, bob.key, carol.key], &[2_000, 3_000, 5_000], 1_000)).unwrap();
+        will_data.inheritors[0].delegate = Some(lawyer.key);
+        will_data.inheritors[1].contingent_of = Some(0);
+        will_data.inheritors[2].delegate = Some(alice.key);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data.try_to_vec().unwrap());
+
+        // The lapsed share is the contingent's, the primary's delegate has no say over it.
+        set_clock(1_000 + CONTINGENCY_SECONDS);
+        let inheritor = alice.info();
+        assert_eq!(
+            Processor::process_claim(&program_id, &lawyer.info(), &will.info(), &[inheritor], None, None, false),
+            Err(WillError::ShareLapsed.into())
+        );
+        // Nor is the primary still an inheritor, they claim for whom they are the delegate.
+        let inheritor = carol.info();
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[inheritor], None, None, false).unwrap();
+        assert_eq!((alice.lamports, carol.lamports), (0, 5_000));
+        Processor::process_claim(&program_id, &bob.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((bob.lamports, lawyer.lamports, will.lamports), (5_000, 0, 0));
+    }
+
+    #[test]
+    fn test_inheritor_who_is_a_delegate_claims_for_both() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut parent, mut child) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        child.is_signer = false;
+        let mut will_data = WillData::unpack(&serialized_will(&[parent.key, child.key], &[5_000, 5_000], 1_000)).unwrap();
+        will_data.inheritors[1].delegate = Some(parent.key);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_001, will_data.try_to_vec().unwrap());
+
+        // Naming the ward as the destination claims the ward's share, not the parent's.
+        let destination = child.info();
+        Processor::process_claim(&program_id, &parent.info(), &will.info(), &[destination], None, None, false).unwrap();
+        assert_eq!((parent.lamports, child.lamports), (0, 5_000));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.inheritors[0].share, will_data.inheritors[1].share), (5_000, 0));
+        Processor::process_claim(&program_id, &parent.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((parent.lamports, child.lamports, will.lamports), (5_001, 5_000, 0));
+    }
+
+    #[test]
+    fn test_process_claim_by_delegate() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut lawyer = TestAccount::wallet(0);
+        let (mut alice, bob) = (TestAccount::wallet(0), Pubkey::new_unique());
+        alice.is_signer = false;
+        let mut will = owned_will(&program_id, &owner, 10_000, serialized_will(&[alice.key, bob], &[4_000, 6_000], 2_000));
+        Processor::process_set_delegate(&program_id, &owner.info(), &will.info(), 0, Some(lawyer.key)).unwrap();
+
+        set_clock(2_000);
+        let inheritor = alice.info();
+        Processor::process_claim(&program_id, &lawyer.info(), &will.info(), &[inheritor], None, None, false).unwrap();
+        assert_eq!(alice.lamports, 4_000);
+        assert_eq!(lawyer.lamports, 0);
+        assert_eq!(will.lamports, 6_000);
+        assert_eq!(take_events().pop(), Some(WillEvent::Claimed { will: will.key, inheritor: alice.key, lamports: 4_000 }));
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].share, 0);
+    }
+
+    #[test]
+    fn test_process_claim_by_unauthorized_delegate() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut lawyer = TestAccount::wallet(0);
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will = owned_will(&program_id, &owner, 10_000, serialized_will(&[alice.key, bob.key], &[4_000, 6_000], 2_000));
+        Processor::process_set_delegate(&program_id, &owner.info(), &will.info(), 0, Some(lawyer.key)).unwrap();
+        let will_data = will.data.clone();
+
+        set_clock(2_000);
+        let mut stranger = TestAccount::wallet(0);
+        let inheritor = alice.info();
+        assert_eq!(
+            Processor::process_claim(&program_id, &stranger.info(), &will.info(), &[inheritor], None, None, false),
+            Err(WillError::NotTheDelegate.into())
+        );
+        // Delegating one inheritor's claims gives no say over another's.
+        let inheritor = bob.info();
+        assert_eq!(
+            Processor::process_claim(&program_id, &lawyer.info(), &will.info(), &[inheritor], None, None, false),
+            Err(WillError::NotTheDelegate.into())
+        );
+        lawyer.is_signer = false;
+        let inheritor = alice.info();
+        assert_eq!(
+            Processor::process_claim(&program_id, &lawyer.info(), &will.info(), &[inheritor], None, None, false),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!((alice.lamports, bob.lamports, will.lamports), (0, 0, 10_000));
+        assert_eq!(will.data, will_data);
+    }
+
+    #[test]
+    fn test_process_claim_rejects_will_as_sender() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, vec![]);
+        will.data = serialized_will(&[will.key], &[10_000], 0);
+        will.is_signer = true;
+
+        let info = will.info();
+        assert_eq!(Processor::process_claim(&program_id, &info, &info, &[], None, None, false), Err(WillError::SenderIsWill.into()));
+        assert_eq!(info.lamports(), 10_000);
+    }
+
+    #[test]
+    fn test_process_initialize() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, vec![0; 1024]);
+
+        Processor::process_initialize(&program_id, &owner.info(), &will.info(), DEFAULT_TIMEOUT_SECONDS).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.owner, owner.key);
+        assert!(will_data.inheritors.is_empty());
+        assert_eq!(
+            Processor::process_initialize(&program_id, &owner.info(), &will.info(), DEFAULT_TIMEOUT_SECONDS),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+    }
+
+    #[test]
+    fn test_process_initialize_from() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (alice, bob, carol, lawyer) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut template_data = WillData::unpack(&serialized_will(&[alice, bob, carol], &[2_000, 3_000, 5_000], 0)).unwrap();
+        template_data.vesting_seconds = 60;
+        template_data.inheritors[1].delegate = Some(lawyer);
+        template_data.inheritors[2].fixed_amount = Some((Pubkey::new_unique(), 1_000));
+        template_data.inheritors[2].claimed = 500;
+        let template_bytes = template_data.try_to_vec().unwrap();
+        let mut template = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, template_bytes.clone());
+        let mut will = owned_will(&program_id, &owner, 10_000_000, vec![0; 1024]);
+
+        let accounts = [template.info()];
+        Processor::process_initialize_from(&program_id, &owner.info(), &will.info(), &accounts, DEFAULT_TIMEOUT_SECONDS).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.owner, owner.key);
+        assert_eq!(will_data.withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(will_data.vesting_seconds, 60);
+        assert_eq!(
+            will_data.inheritors,
+            vec![
+                InheritorInfo { claimed: 0, ..template_data.inheritors[0].clone() },
+                InheritorInfo { claimed: 0, ..template_data.inheritors[1].clone() },
+                InheritorInfo { claimed: 0, ..template_data.inheritors[2].clone() },
+            ]
+        );
+        assert_eq!(template.data, template_bytes);
+        assert_eq!(take_events().pop(), Some(WillEvent::Initialized { will: will.key, owner: owner.key }));
+    }
+
+    #[test]
+    fn test_process_initialize_from_rejects_foreign_templates() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, vec![0; 1024]);
+        let mut foreign = TestAccount::will(Pubkey::new_unique(), &Pubkey::new_unique(), 10_000, serialized_will(&[Pubkey::new_unique()], &[1], 0));
+        let accounts = [foreign.info()];
+        assert_eq!(
+            Processor::process_initialize_from(&program_id, &owner.info(), &will.info(), &accounts, DEFAULT_TIMEOUT_SECONDS),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            Processor::process_initialize_from(&program_id, &owner.info(), &will.info(), &[], DEFAULT_TIMEOUT_SECONDS),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+
+        // A template listing the new owner would make them their own heir.
+        let mut template = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[owner.key], &[1], 0));
+        let accounts = [template.info()];
+        assert_eq!(
+            Processor::process_initialize_from(&program_id, &owner.info(), &will.info(), &accounts, DEFAULT_TIMEOUT_SECONDS),
+            Err(WillError::OwnerIsInheritor.into())
+        );
+        assert_eq!(will.data, vec![0; 1024]);
+    }
+
+    #[test]
+    fn test_process_initialize_requires_rent_exemption() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let rent_reserve = Rent::default().minimum_balance(1024);
+        let mut will = owned_will(&program_id, &owner, rent_reserve - 1, vec![0; 1024]);
+
+        assert_eq!(
+            Processor::process_initialize(&program_id, &owner.info(), &will.info(), DEFAULT_TIMEOUT_SECONDS),
+            Err(ProgramError::AccountNotRentExempt)
+        );
+        assert_eq!(will.data, vec![0; 1024]);
+        will.lamports += 1;
+        Processor::process_initialize(&program_id, &owner.info(), &will.info(), DEFAULT_TIMEOUT_SECONDS).unwrap();
+    }
+
+    #[test]
+    fn test_process_heartbeat() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[], &[], 0));
+
+        Processor::process_heartbeat(&program_id, &owner.info(), &will.info()).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.last_heartbeat_ts, 1_000);
+        assert_eq!(will_data.withdraw_allowed_ts, 1_000 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(will.lamports, 10_000_000);
+
+        set_clock(1_100);
+        take_logs();
+        Processor::process_heartbeat(&program_id, &owner.info(), &will.info()).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.last_heartbeat_ts, 1_100);
+        assert_eq!(will_data.withdraw_allowed_ts, will_data.last_heartbeat_ts + will_data.timeout_seconds);
+        let expected = format!("timer {} -> {}", 1_000 + DEFAULT_TIMEOUT_SECONDS, 1_100 + DEFAULT_TIMEOUT_SECONDS);
+        assert_eq!(take_logs(), vec![expected]);
+    }
+
+    #[test]
+    fn test_process_claim_with_tokens_requires_token_accounts() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, serialized_will(&[inheritor.key], &[10_000], 0));
+
+        assert_eq!(
+            Processor::process_claim_with_tokens(&program_id, &inheritor.info(), &will.info(), &[]),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(will.lamports, 10_000);
+    }
+
+    #[test]
+    fn test_process_claim_with_tokens_survives_reentry() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, bob) = (TestAccount::wallet(0), Pubkey::new_unique());
+        let mut will_tokens = TestAccount::wallet(0);
+        let mut will_data = WillData::unpack(&serialized_will(&[alice.key, bob], &[4_000, 6_000], 0)).unwrap();
+        will_data.token_accounts.push(will_tokens.key);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, will_data.try_to_vec().unwrap());
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = find_token_authority(&will.key, &program_id).0;
+        will_tokens.owner = spl_token::id();
+        will_tokens.data = vec![0; spl_token::state::Account::LEN];
+        let balance = spl_token::state::Account { amount: 1_000, state: spl_token::state::AccountState::Initialized, ..Default::default() };
+        spl_token::state::Account::pack(balance, &mut will_tokens.data).unwrap();
+
+        // A malicious token program, handed the will as the destination, claims alice's
+        // share once more in the middle of the transfer.
+        let (alice_key, reentries) = (alice.key, Rc::new(RefCell::new(vec![])));
+        let recorded = Rc::clone(&reentries);
+        set_invoke(Some(Box::new(move |_, account_infos| {
+            // Leaked to outlive the accounts it is invoked with.
+            let sender = Box::leak(Box::new(TestAccount::wallet(0)));
+            sender.key = alice_key;
+            let accounts = [sender.info(), account_infos[1].clone()];
+            let result = Processor::process(account_infos[1].owner, &accounts, &[2]);
+            recorded.borrow_mut().push((result, accounts[0].lamports()));
+            Ok(())
+        })));
+        let will_info = will.info();
+        let accounts = [token_program.info(), token_authority.info(), will_tokens.info(), will_info.clone()];
+        let result = Processor::process_claim_with_tokens(&program_id, &alice.info(), &will_info, &accounts);
+        set_invoke(None);
+        result.unwrap();
+        drop((accounts, will_info));
+
+        assert_eq!(*reentries.borrow(), vec![(Err(WillError::AlreadyClaimed.into()), 0)]);
+        assert_eq!((alice.lamports, will.lamports), (4_000, 6_000));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.inheritors[0].share, will_data.inheritors[0].claimed), (0, 4_000));
+    }
+
+    #[test]
+    fn test_process_claim_with_tokens_pays_fixed_amounts_first() {
+        let usdc = Pubkey::new_unique();
+        let distribute = |fixed_amounts: [Option<u64>; 2], balance: u64| {
+            set_clock(1_000);
+            let program_id = Pubkey::new_unique();
+            let mut owner = TestAccount::wallet(0);
+            let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+            let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice.key, bob.key], &[5_000, 5_000], 2_000));
+            for (index, fixed_amount) in fixed_amounts.iter().enumerate() {
+                let fixed_amount = fixed_amount.map(|amount| (usdc, amount));
+                Processor::process_set_fixed_amount(&program_id, &owner.info(), &will.info(), index as u8, fixed_amount).unwrap();
+            }
+            assert_eq!(
+                take_events().pop(),
+                Some(WillEvent::FixedAmountChanged { will: will.key, inheritor: bob.key, fixed_amount: fixed_amounts[1].map(|amount| (usdc, amount)) })
+            );
+
+            let mut will_tokens = will_token_account(&will, &program_id, usdc, balance);
+            Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_tokens.info()]).unwrap();
+
+            set_clock(2_000);
+            let mut token_program = TestAccount::wallet(0);
+            token_program.key = spl_token::id();
+            let mut token_authority = TestAccount::wallet(0);
+            token_authority.key = find_token_authority(&will.key, &program_id).0;
+            let (mut alice_tokens, mut bob_tokens) = (token_account(usdc, 0), token_account(usdc, 0));
+            mock_token_transfers();
+            for (inheritor, tokens) in [(&mut bob, &mut bob_tokens), (&mut alice, &mut alice_tokens)] {
+                let accounts = [token_program.info(), token_authority.info(), will_tokens.info(), tokens.info()];
+                Processor::process_claim_with_tokens(&program_id, &inheritor.info(), &will.info(), &accounts).unwrap();
+            }
+            set_invoke(None);
+            assert_eq!(token_balance(&will_tokens), 0);
+            (token_balance(&alice_tokens), token_balance(&bob_tokens))
+        };
+
+        assert_eq!(distribute([None, None], 3_000), (1_500, 1_500));
+        assert_eq!(distribute([None, Some(1_000)], 3_000), (1_000, 2_000));
+        // Fixed amounts exceeding the balance share it in proportion.
+        assert_eq!(distribute([Some(1_000), Some(1_000)], 500), (250, 250));
+        assert_eq!(distribute([None, Some(5_000)], 3_000), (0, 3_000));
+    }
+
+    #[test]
+    fn test_fixed_amounts_only_apply_to_their_mint() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice.key, bob.key], &[5_000, 5_000], 2_000));
+        let (usdc, bonk) = (Pubkey::new_unique(), Pubkey::new_unique());
+        Processor::process_set_fixed_amount(&program_id, &owner.info(), &will.info(), 0, Some((usdc, 1_000))).unwrap();
+        let mut will_usdc = will_token_account(&will, &program_id, usdc, 3_000);
+        let mut will_bonk = will_token_account(&will, &program_id, bonk, 3_000);
+        for will_tokens in [&mut will_usdc, &mut will_bonk] {
+            Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_tokens.info()]).unwrap();
+        }
+
+        set_clock(2_000);
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = find_token_authority(&will.key, &program_id).0;
+        let (mut alice_usdc, mut alice_bonk) = (token_account(usdc, 0), token_account(bonk, 0));
+        let (mut bob_usdc, mut bob_bonk) = (token_account(usdc, 0), token_account(bonk, 0));
+        mock_token_transfers();
+        // Both mints in a single claim, only the USDC account pays the fixed amount first.
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), alice_usdc.info(), will_bonk.info(), alice_bonk.info()];
+        Processor::process_claim_with_tokens(&program_id, &alice.info(), &will.info(), &accounts).unwrap();
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), bob_usdc.info(), will_bonk.info(), bob_bonk.info()];
+        Processor::process_claim_with_tokens(&program_id, &bob.info(), &will.info(), &accounts).unwrap();
+        set_invoke(None);
+        assert_eq!((token_balance(&alice_usdc), token_balance(&bob_usdc)), (2_000, 1_000));
+        assert_eq!((token_balance(&alice_bonk), token_balance(&bob_bonk)), (1_500, 1_500));
+        assert_eq!((token_balance(&will_usdc), token_balance(&will_bonk)), (0, 0));
+    }
+
+    #[test]
+    fn test_process_add_token_account() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+        let mut will_usdc = will_token_account(&will, &program_id, Pubkey::new_unique(), 0);
+
+        take_events();
+        Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_usdc.info()]).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().token_accounts, vec![will_usdc.key]);
+        assert_eq!(take_events(), vec![WillEvent::TokenAccountAdded { will: will.key, token_account: will_usdc.key }]);
+        assert_eq!(
+            Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_usdc.info()]),
+            Err(ProgramError::InvalidArgument)
+        );
+        // Only accounts the will can sign for are worth listing.
+        let mut foreign = token_account(Pubkey::new_unique(), 0);
+        assert_eq!(
+            Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[foreign.info()]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut wallet = TestAccount::wallet(0);
+        assert_eq!(
+            Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[wallet.info()]),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        let mut stranger = TestAccount::wallet(0);
+        let mut will_bonk = will_token_account(&will, &program_id, Pubkey::new_unique(), 0);
+        assert_eq!(
+            Processor::process_add_token_account(&program_id, &stranger.info(), &will.info(), &[will_bonk.info()]),
+            Err(WillError::NotTheOwner.into())
+        );
+    }
+
+    #[test]
+    fn test_claim_with_tokens_rejects_repeated_and_unlisted_accounts() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut alice = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice.key, Pubkey::new_unique()], &[2_500, 7_500], 2_000));
+        let usdc = Pubkey::new_unique();
+        let mut will_usdc = will_token_account(&will, &program_id, usdc, 1_000);
+        Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_usdc.info()]).unwrap();
+
+        set_clock(2_000);
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = find_token_authority(&will.key, &program_id).0;
+        let mut alice_usdc = token_account(usdc, 0);
+        mock_token_transfers();
+        // Each pair would be paid a quarter of the balance before any transfer.
+        let (source, destination) = (will_usdc.info(), alice_usdc.info());
+        let accounts = [token_program.info(), token_authority.info(), source.clone(), destination.clone(), source.clone(), destination.clone(), source, destination];
+        assert_eq!(
+            Processor::process_claim_with_tokens(&program_id, &alice.info(), &will.info(), &accounts),
+            Err(ProgramError::InvalidArgument)
+        );
+        drop(accounts);
+        let mut unlisted = will_token_account(&will, &program_id, usdc, 1_000);
+        let destination = alice_usdc.info();
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), destination.clone(), unlisted.info(), destination];
+        assert_eq!(
+            Processor::process_claim_with_tokens(&program_id, &alice.info(), &will.info(), &accounts),
+            Err(WillError::TokenAccountNotListed.into())
+        );
+        drop(accounts);
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), alice_usdc.info()];
+        Processor::process_claim_with_tokens(&program_id, &alice.info(), &will.info(), &accounts).unwrap();
+        set_invoke(None);
+        assert_eq!((token_balance(&alice_usdc), token_balance(&will_usdc)), (250, 750));
+    }
+
+    #[test]
+    fn test_sol_claim_first_tokens_afterwards() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice.key, bob.key], &[5_000, 5_000], 2_000));
+        let usdc = Pubkey::new_unique();
+        let mut will_usdc = will_token_account(&will, &program_id, usdc, 3_000);
+        Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_usdc.info()]).unwrap();
+
+        set_clock(2_000);
+        // A plain claim pays the lamports but keeps the share open for the listed tokens.
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, None, false).unwrap();
+        assert!(alice.lamports > 0);
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].share, 5_000);
+
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = find_token_authority(&will.key, &program_id).0;
+        let (mut alice_usdc, mut bob_usdc) = (token_account(usdc, 0), token_account(usdc, 0));
+        mock_token_transfers();
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), bob_usdc.info()];
+        Processor::process_claim_with_tokens(&program_id, &bob.info(), &will.info(), &accounts).unwrap();
+        assert_eq!(token_balance(&bob_usdc), 1_500);
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), alice_usdc.info()];
+        Processor::process_claim_with_tokens(&program_id, &alice.info(), &will.info(), &accounts).unwrap();
+        set_invoke(None);
+        assert_eq!((token_balance(&alice_usdc), token_balance(&will_usdc)), (1_500, 0));
+        // The lamports were already paid, the token claim only closes the will.
+        assert_eq!(alice.lamports + bob.lamports + will.lamports, 10_000_000);
+        assert_eq!(will.lamports, 0);
+        assert!(will.data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_process_set_fixed_amount() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+
+        let usdc = Pubkey::new_unique();
+        Processor::process_set_fixed_amount(&program_id, &owner.info(), &will.info(), 0, Some((usdc, 1_000))).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().inheritors[0].fixed_amount, Some((usdc, 1_000)));
+        assert_eq!(
+            Processor::process_set_fixed_amount(&program_id, &owner.info(), &will.info(), 1, None),
+            Err(WillError::InheritorIndexOutOfBounds.into())
+        );
+        let mut stranger = TestAccount::wallet(0);
+        assert_eq!(
+            Processor::process_set_fixed_amount(&program_id, &stranger.info(), &will.info(), 0, None),
+            Err(WillError::NotTheOwner.into())
+        );
+        set_clock(2_000);
+        assert_eq!(
+            Processor::process_set_fixed_amount(&program_id, &owner.info(), &will.info(), 0, None),
+            Err(WillError::AlreadyReleased.into())
+        );
+    }
+
+    #[test]
+    fn test_process_deposit_requires_system_program() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(1_000);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[], &[], 2_000));
+        let mut not_system = TestAccount::wallet(0);
+
+        assert_eq!(
+            Processor::process_deposit(&program_id, &owner.info(), &will.info(), &[not_system.info()], 1_000),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            Processor::process_deposit(&program_id, &owner.info(), &will.info(), &[], 1_000),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!((owner.lamports, will.lamports), (1_000, 10_000_000));
+    }
+
+    #[test]
+    fn test_process_preview_claim() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut inheritor = TestAccount::wallet(0);
+        let data = serialized_will(&[inheritor.key, Pubkey::new_unique()], &[2_500, 7_500], 0);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, data.clone());
+
+        take_logs();
+        Processor::process_preview_claim(&inheritor.info(), &will.info()).unwrap();
+        let expected = format!("CLAIMABLE inheritor={} shares=2500/10000 lamports=2500", inheritor.key);
+        assert_eq!(take_logs(), vec![expected]);
+        assert_eq!(will.data, data);
+    }
+
+    #[test]
+    fn test_process_distribute() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut executor = TestAccount::wallet(0);
+        let (mut alice, mut bob, mut carol) = (TestAccount::wallet(0), TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will_data = WillData::unpack(&serialized_will(&[alice.key, bob.key, carol.key], &[1, 2, 4], 1_000)).unwrap();
+        will_data.executor = Some(executor.key);
+        let data = will_data.try_to_vec().unwrap();
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 7_001, data.clone());
+
+        // One inheritor missing fails the whole distribution before anything is paid.
+        let accounts = [carol.info(), alice.info()];
+        assert_eq!(
+            Processor::process_distribute(&executor.info(), &will.info(), &accounts),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!((alice.lamports, carol.lamports, will.lamports), (0, 0, 7_001));
+        assert_eq!(will.data, data);
+        let mut stranger = TestAccount::wallet(0);
+        let accounts = [carol.info(), alice.info(), bob.info()];
+        assert_eq!(
+            Processor::process_distribute(&stranger.info(), &will.info(), &accounts),
+            Err(WillError::NotTheExecutor.into())
+        );
+
+        take_events();
+        let accounts = [carol.info(), alice.info(), bob.info()];
+        Processor::process_distribute(&executor.info(), &will.info(), &accounts).unwrap();
+        assert_eq!((alice.lamports, bob.lamports, carol.lamports, will.lamports), (1_000, 2_000, 4_001, 0));
+        assert_eq!(take_return_data(), 7_000_u64.to_le_bytes());
+        assert_eq!(
+            take_events(),
+            vec![
+                WillEvent::Claimed { will: will.key, inheritor: alice.key, lamports: 1_000 },
+                WillEvent::Claimed { will: will.key, inheritor: bob.key, lamports: 2_000 },
+                WillEvent::Claimed { will: will.key, inheritor: carol.key, lamports: 4_000 },
+                WillEvent::Closed { will: will.key, recipient: carol.key, lamports: 1 },
+            ]
+        );
+        assert!(will.data.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn test_process_distribute_before_release() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut executor = TestAccount::wallet(0);
+        let mut alice = TestAccount::wallet(0);
+        let mut will_data = WillData::unpack(&serialized_will(&[alice.key], &[1], 2_000)).unwrap();
+        will_data.executor = Some(executor.key);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 7_000, will_data.try_to_vec().unwrap());
+
+        let accounts = [alice.info()];
+        assert_eq!(
+            Processor::process_distribute(&executor.info(), &will.info(), &accounts),
+            Err(WillError::NotYetReleased.into())
+        );
+        assert_eq!(will.lamports, 7_000);
+    }
+
+    #[test]
+    fn test_process_query_release_status() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let data = serialized_will(&[Pubkey::new_unique()], &[10_000], 1_600);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000, data.clone());
+
+        Processor::process_query_release_status(&will.info()).unwrap();
+        let status = ReleaseStatus::try_from_slice(&take_return_data()).unwrap();
+        assert_eq!(status, ReleaseStatus { released: false, withdraw_allowed_ts: 1_600, seconds_remaining: 600 });
+        assert_eq!(will.data, data);
+
+        set_clock(1_600);
+        Processor::process_query_release_status(&will.info()).unwrap();
+        let status = ReleaseStatus::try_from_slice(&take_return_data()).unwrap();
+        assert_eq!(status, ReleaseStatus { released: true, withdraw_allowed_ts: 1_600, seconds_remaining: 0 });
+        set_clock(5_000);
+        Processor::process_query_release_status(&will.info()).unwrap();
+        let status = ReleaseStatus::try_from_slice(&take_return_data()).unwrap();
+        assert_eq!(status, ReleaseStatus { released: true, withdraw_allowed_ts: 1_600, seconds_remaining: 0 });
+        assert_eq!(will.data, data);
+    }
+
+    #[test]
+    fn test_process_migrate() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let heir = Pubkey::new_unique();
+        let legacy = WillDataV4 {
+            schema_version: 4,
+            withdraw_allowed_ts: 2_000,
+            inheritors_names: vec![String::from("heir")],
+            inheritors_pubkeys: vec![heir],
+            inheritors_shares: vec![10_000],
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            owner: Pubkey::default(),
+        };
+        let mut data = legacy.try_to_vec().unwrap();
+        data.resize(1024, 0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, data);
+
+        Processor::process_migrate(&program_id, &owner.info(), &will.info()).unwrap();
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.schema_version, SCHEMA_VERSION);
+        assert_eq!(will_data.owner, owner.key);
+        assert_eq!(will_data.inheritors[0].pubkey, heir);
+        assert_eq!(
+            Processor::process_migrate(&program_id, &owner.info(), &will.info()),
+            Err(WillError::AlreadyMigrated.into())
+        );
+    }
+
+    #[test]
+    fn test_process_revoke() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+
+        Processor::process_revoke(&program_id, &owner.info(), &will.info(), &[]).unwrap();
+        assert_eq!(owner.lamports, 10_000_000);
+        assert_eq!(will.lamports, 0);
+        assert!(will.data.iter().all(|byte| *byte == 0));
+        assert_eq!(take_return_data(), 10_000_000_u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_process_revoke_returns_token_accounts() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+        let usdc = Pubkey::new_unique();
+        let mut will_usdc = will_token_account(&will, &program_id, usdc, 3_000);
+        Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_usdc.info()]).unwrap();
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = find_token_authority(&will.key, &program_id).0;
+        let mut owner_usdc = token_account_held_by(owner.key, usdc, 0);
+
+        // The listed token account can not be left behind with the will's token authority.
+        assert_eq!(
+            Processor::process_revoke(&program_id, &owner.info(), &will.info(), &[]),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        let mut foreign = token_account(usdc, 0);
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), foreign.info()];
+        assert_eq!(
+            Processor::process_revoke(&program_id, &owner.info(), &will.info(), &accounts),
+            Err(ProgramError::InvalidAccountData)
+        );
+        drop(accounts);
+        assert_eq!(will.lamports, 10_000_000);
+
+        mock_token_transfers();
+        let accounts = [token_program.info(), token_authority.info(), will_usdc.info(), owner_usdc.info()];
+        let result = Processor::process_revoke(&program_id, &owner.info(), &will.info(), &accounts);
+        set_invoke(None);
+        result.unwrap();
+        drop(accounts);
+        assert_eq!((token_balance(&owner_usdc), token_balance(&will_usdc)), (3_000, 0));
+        assert_eq!((owner.lamports, will.lamports), (10_000_000, 0));
+    }
+
+    #[test]
+    fn test_process_transfer_ownership() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut new_owner = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+
+        Processor::process_transfer_ownership(&program_id, &owner.info(), &will.info(), new_owner.key).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().owner, new_owner.key);
+        assert_eq!(
+            take_events().pop(),
+            Some(WillEvent::OwnershipTransferred { will: will.key, previous_owner: owner.key, new_owner: new_owner.key })
+        );
+
+        // The will address still derives from the first owner, only the new one may act on it.
+        Processor::process_heartbeat(&program_id, &new_owner.info(), &will.info()).unwrap();
+        assert_eq!(Processor::process_heartbeat(&program_id, &owner.info(), &will.info()), Err(WillError::NotTheOwner.into()));
+    }
+
+    #[test]
+    fn test_process_transfer_ownership_unauthorized() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let heir = Pubkey::new_unique();
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[heir], &[10_000], 2_000));
+        Processor::process_heartbeat(&program_id, &owner.info(), &will.info()).unwrap();
+        let will_data = will.data.clone();
+
+        let mut stranger = TestAccount::wallet(0);
+        let stranger_key = stranger.key;
+        assert_eq!(
+            Processor::process_transfer_ownership(&program_id, &stranger.info(), &will.info(), stranger_key),
+            Err(WillError::NotTheOwner.into())
+        );
+        owner.is_signer = false;
+        assert_eq!(
+            Processor::process_transfer_ownership(&program_id, &owner.info(), &will.info(), stranger_key),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        owner.is_signer = true;
+        assert_eq!(
+            Processor::process_transfer_ownership(&program_id, &owner.info(), &will.info(), Pubkey::default()),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            Processor::process_transfer_ownership(&program_id, &owner.info(), &will.info(), heir),
+            Err(WillError::OwnerIsInheritor.into())
+        );
+        assert_eq!(will.data, will_data);
+    }
+
+    #[test]
+    fn test_process_set_fallback() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let charity = Pubkey::new_unique();
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+
+        Processor::process_set_fallback(&program_id, &owner.info(), &will.info(), Some(charity)).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().fallback, Some(charity));
+        assert_eq!(take_events().pop(), Some(WillEvent::FallbackChanged { will: will.key, fallback: Some(charity) }));
+        let mut stranger = TestAccount::wallet(0);
+        assert_eq!(
+            Processor::process_set_fallback(&program_id, &stranger.info(), &will.info(), None),
+            Err(WillError::NotTheOwner.into())
+        );
+    }
+
+    #[test]
+    fn test_process_set_min_claim_interval() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (mut inheritor, mut other) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[inheritor.key, other.key], &[5_000, 5_000], 2_000));
+
+        Processor::process_set_min_claim_interval(&program_id, &owner.info(), &will.info(), 60).unwrap();
+        assert_eq!(take_events().pop(), Some(WillEvent::MinClaimIntervalChanged { will: will.key, seconds: 60 }));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!((will_data.min_claim_interval, will_data.last_action_ts), (60, 1_000));
+
+        // A second heartbeat within the interval is rejected, one after it succeeds.
+        set_clock(1_059);
+        let before = will.data.clone();
+        assert_eq!(Processor::process_heartbeat(&program_id, &owner.info(), &will.info()), Err(WillError::ActionTooSoon.into()));
+        assert_eq!(will.data, before);
+        set_clock(1_060);
+        Processor::process_heartbeat(&program_id, &owner.info(), &will.info()).unwrap();
+        assert_eq!(WillData::unpack(&will.data).unwrap().last_action_ts, 1_060);
+
+        // Claims are not throttled, one inheritor's partial claim holds up nobody.
+        set_clock(1_060 + DEFAULT_TIMEOUT_SECONDS);
+        Processor::process_heartbeat(&program_id, &owner.info(), &will.info()).unwrap();
+        set_clock(1_060 + 2 * DEFAULT_TIMEOUT_SECONDS);
+        Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, Some(1_000), false).unwrap();
+        Processor::process_claim(&program_id, &inheritor.info(), &will.info(), &[], None, Some(1_000), false).unwrap();
+        Processor::process_claim(&program_id, &other.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!((inheritor.lamports, other.lamports), (2_000, 5_000_000));
+        assert_eq!(WillData::unpack(&will.data).unwrap().last_action_ts, 1_060 + DEFAULT_TIMEOUT_SECONDS);
+
+        set_clock(1_120 + 2 * DEFAULT_TIMEOUT_SECONDS);
+        for seconds in [-1, MAX_ACTION_INTERVAL_SECONDS + 1] {
+            assert_eq!(
+                Processor::process_set_min_claim_interval(&program_id, &owner.info(), &will.info(), seconds),
+                Err(ProgramError::InvalidArgument)
+            );
+        }
+        Processor::process_set_min_claim_interval(&program_id, &owner.info(), &will.info(), 0).unwrap();
+        Processor::process_heartbeat(&program_id, &owner.info(), &will.info()).unwrap();
+        let mut stranger = TestAccount::wallet(0);
+        assert_eq!(
+            Processor::process_set_min_claim_interval(&program_id, &stranger.info(), &will.info(), 60),
+            Err(WillError::NotTheOwner.into())
+        );
+    }
+
+    #[test]
+    fn test_process_sweep_to_fallback() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut charity = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+        Processor::process_set_fallback(&program_id, &owner.info(), &will.info(), Some(charity.key)).unwrap();
+
+        set_clock(2_000 + FALLBACK_AFTER_SECONDS);
+        let mut caller = TestAccount::wallet(0);
+        let fallback = charity.info();
+        Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &[fallback]).unwrap();
+        assert_eq!(charity.lamports, 10_000_000);
+        assert_eq!(will.lamports, 0);
+        assert!(will.data.iter().all(|byte| *byte == 0));
+        assert_eq!(take_events().pop(), Some(WillEvent::SweptToFallback { will: will.key, fallback: charity.key, lamports: 10_000_000 }));
+    }
+
+    #[test]
+    fn test_process_sweep_to_fallback_waits_for_vesting() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut charity = TestAccount::wallet(0);
+        let vesting_seconds = 2 * FALLBACK_AFTER_SECONDS;
+        let mut will_data = WillData::unpack(&serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000)).unwrap();
+        will_data.vesting_seconds = vesting_seconds;
+        will_data.fallback = Some(charity.key);
+        let mut will = TestAccount::will(Pubkey::new_unique(), &program_id, 10_000_000, will_data.try_to_vec().unwrap());
+
+        // A year after the release half of the share is still vesting.
+        set_clock(2_000 + FALLBACK_AFTER_SECONDS);
+        let mut caller = TestAccount::wallet(0);
+        assert_eq!(
+            Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &[charity.info()]),
+            Err(WillError::FallbackNotDue.into())
+        );
+        set_clock(2_000 + vesting_seconds);
+        Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &[charity.info()]).unwrap();
+        assert_eq!((charity.lamports, will.lamports), (10_000_000, 0));
+    }
+
+    #[test]
+    fn test_process_sweep_to_fallback_returns_token_accounts() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut charity = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+        Processor::process_set_fallback(&program_id, &owner.info(), &will.info(), Some(charity.key)).unwrap();
+        let usdc = Pubkey::new_unique();
+        let mut will_usdc = will_token_account(&will, &program_id, usdc, 3_000);
+        Processor::process_add_token_account(&program_id, &owner.info(), &will.info(), &[will_usdc.info()]).unwrap();
+
+        set_clock(2_000 + FALLBACK_AFTER_SECONDS);
+        let mut caller = TestAccount::wallet(0);
+        let mut token_program = TestAccount::wallet(0);
+        token_program.key = spl_token::id();
+        let mut token_authority = TestAccount::wallet(0);
+        token_authority.key = find_token_authority(&will.key, &program_id).0;
+        // Anyone may sweep, so the tokens may only go to the fallback.
+        let mut caller_usdc = token_account_held_by(caller.key, usdc, 0);
+        let accounts = [charity.info(), token_program.info(), token_authority.info(), will_usdc.info(), caller_usdc.info()];
+        assert_eq!(
+            Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &accounts),
+            Err(ProgramError::InvalidAccountData)
+        );
+        drop(accounts);
+        assert_eq!(
+            Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &[charity.info()]),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        assert_eq!(will.lamports, 10_000_000);
+
+        let mut charity_usdc = token_account_held_by(charity.key, usdc, 0);
+        mock_token_transfers();
+        let accounts = [charity.info(), token_program.info(), token_authority.info(), will_usdc.info(), charity_usdc.info()];
+        let result = Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &accounts);
+        set_invoke(None);
+        result.unwrap();
+        drop(accounts);
+        assert_eq!((token_balance(&charity_usdc), token_balance(&will_usdc)), (3_000, 0));
+        assert_eq!((charity.lamports, will.lamports), (10_000_000, 0));
+    }
+
+    #[test]
+    fn test_process_sweep_to_fallback_too_early() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let mut charity = TestAccount::wallet(0);
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[Pubkey::new_unique()], &[10_000], 2_000));
+        Processor::process_set_fallback(&program_id, &owner.info(), &will.info(), Some(charity.key)).unwrap();
+        let will_data = will.data.clone();
+
+        // Released, but not for long enough.
+        set_clock(2_000 + FALLBACK_AFTER_SECONDS - 1);
+        let mut caller = TestAccount::wallet(0);
+        let fallback = charity.info();
+        assert_eq!(
+            Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &[fallback]),
+            Err(WillError::FallbackNotDue.into())
+        );
+        set_clock(2_000 + FALLBACK_AFTER_SECONDS);
+        let mut impostor = TestAccount::wallet(0);
+        let fallback = impostor.info();
+        assert_eq!(
+            Processor::process_sweep_to_fallback(&program_id, &caller.info(), &will.info(), &[fallback]),
+            Err(WillError::NotTheFallback.into())
+        );
+        assert_eq!((charity.lamports, impostor.lamports, will.lamports), (0, 0, 10_000_000));
+        assert_eq!(will.data, will_data);
+    }
+
+    #[test]
+    fn test_process_vote_extension() {
+        set_clock(3_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let carol = Pubkey::new_unique();
+        let mut will = TestAccount::will(
+            Pubkey::new_unique(), &program_id, 10_000_000, serialized_will(&[alice.key, bob.key, carol], &[3_000, 3_000, 4_000], 2_000));
+
+        Processor::process_vote_extension(&alice.info(), &will.info()).unwrap();
+        assert_eq!(take_events().pop(), Some(WillEvent::ExtensionVoted { will: will.key, inheritor: alice.key, votes: 1 }));
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000);
+        assert_eq!(Processor::process_vote_extension(&alice.info(), &will.info()), Err(WillError::AlreadyVoted.into()));
+        let mut stranger = TestAccount::wallet(0);
+        assert_eq!(Processor::process_vote_extension(&stranger.info(), &will.info()), Err(WillError::NotAnInheritor.into()));
+
+        Processor::process_vote_extension(&bob.info(), &will.info()).unwrap();
+        let events = take_events();
+        assert_eq!(
+            events[events.len() - 2..],
+            [
+                WillEvent::ExtensionVoted { will: will.key, inheritor: bob.key, votes: 2 },
+                WillEvent::ReleaseExtended { will: will.key, withdraw_allowed_ts: 3_000 + GRACE_EXTENSION_SECONDS },
+            ]
+        );
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.withdraw_allowed_ts, 3_000 + GRACE_EXTENSION_SECONDS);
+        assert!(will_data.extension_votes.is_empty());
+        assert_eq!(
+            Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, None, false),
+            Err(WillError::NotYetReleased.into())
+        );
+    }
+
+    #[test]
+    fn test_extensions_are_bounded_and_keep_the_owner_out() {
+        set_clock(1_000);
+        let program_id = Pubkey::new_unique();
+        let mut owner = TestAccount::wallet(0);
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let mut will = owned_will(&program_id, &owner, 10_000_000, serialized_will(&[alice.key, bob.key], &[5_000, 5_000], 2_000));
+
+        set_clock(2_000);
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, Some(1_000), false).unwrap();
+        for extension in 1..=MAX_EXTENSIONS as i64 {
+            Processor::process_vote_extension(&alice.info(), &will.info()).unwrap();
+            Processor::process_vote_extension(&bob.info(), &will.info()).unwrap();
+            assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000 + extension * GRACE_EXTENSION_SECONDS);
+            // The release moved past now, the owner still may not change a will being claimed.
+            assert_eq!(
+                Processor::process_set_delegate(&program_id, &owner.info(), &will.info(), 0, Some(Pubkey::new_unique())),
+                Err(WillError::AlreadyReleased.into())
+            );
+        }
+        assert_eq!(Processor::process_vote_extension(&alice.info(), &will.info()), Err(WillError::TooManyExtensions.into()));
+        assert_eq!(WillData::unpack(&will.data).unwrap().extensions, MAX_EXTENSIONS);
+    }
+
+    #[test]
+    fn test_process_vote_extension_below_quorum() {
+        set_clock(3_000);
+        let program_id = Pubkey::new_unique();
+        let (mut alice, mut bob) = (TestAccount::wallet(0), TestAccount::wallet(0));
+        let (carol, dave) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut will = TestAccount::will(
+            Pubkey::new_unique(), &program_id, 10_000_000, serialized_will(&[alice.key, bob.key, carol, dave], &[2_500; 4], 2_000));
+
+        // Half of the inheritors is not a quorum.
+        Processor::process_vote_extension(&alice.info(), &will.info()).unwrap();
+        Processor::process_vote_extension(&bob.info(), &will.info()).unwrap();
+        assert_eq!(take_events().pop(), Some(WillEvent::ExtensionVoted { will: will.key, inheritor: bob.key, votes: 2 }));
+        let will_data = WillData::unpack(&will.data).unwrap();
+        assert_eq!(will_data.withdraw_allowed_ts, 2_000);
+        assert_eq!(will_data.extension_votes, vec![alice.key, bob.key]);
+
+        // Inheritors who claimed their share have no say any more.
+        Processor::process_claim(&program_id, &alice.info(), &will.info(), &[], None, None, false).unwrap();
+        assert_eq!(Processor::process_vote_extension(&alice.info(), &will.info()), Err(WillError::NotAnInheritor.into()));
+        assert_eq!(WillData::unpack(&will.data).unwrap().withdraw_allowed_ts, 2_000);
+    }
+}
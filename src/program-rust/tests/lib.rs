@@ -1,96 +1,176 @@
-use borsh::BorshDeserialize;
-use helloworld::{process_instruction, GreetingAccount};
+use helloworld::{
+    derive_will_address, error::WillError, instruction::{self, SetInheritenceMessage}, process_instruction, WillData, MIN_TIMEOUT_SECONDS,
+    WILL_SEED,
+};
 use solana_program_test::*;
 use solana_sdk::{
-    account::Account,
-    instruction::{AccountMeta, Instruction},
+    clock::Clock,
+    instruction::{Instruction, InstructionError},
     pubkey::Pubkey,
-    signature::Signer,
-    transaction::Transaction,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
 };
-use std::mem;
+
+const WILL_SPACE: u64 = 1024;
+const ESTATE_LAMPORTS: u64 = 1_000_000_000;
+
+async fn send(context: &mut ProgramTestContext, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransportError> {
+    let mut all_signers = vec![&context.payer];
+    all_signers.extend_from_slice(signers);
+    let recent_blockhash = context.banks_client.get_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(instructions, Some(&context.payer.pubkey()), &all_signers, recent_blockhash);
+    context.banks_client.process_transaction(transaction).await
+}
+
+async fn fetch_will(context: &mut ProgramTestContext, will: Pubkey) -> WillData {
+    let account = context
+        .banks_client
+        .get_account(will)
+        .await
+        .expect("get_account")
+        .expect("will not found");
+    WillData::unpack(&account.data).unwrap()
+}
+
+async fn create_will(context: &mut ProgramTestContext, program_id: &Pubkey, owner: &Keypair) -> (Pubkey, u64) {
+    let will = derive_will_address(&owner.pubkey(), program_id).unwrap();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let rent_reserve = rent.minimum_balance(WILL_SPACE as usize);
+    let create = system_instruction::create_account_with_seed(
+        &context.payer.pubkey(),
+        &will,
+        &owner.pubkey(),
+        WILL_SEED,
+        rent_reserve + ESTATE_LAMPORTS,
+        WILL_SPACE,
+        program_id,
+    );
+    let initialize = instruction::initialize(program_id, &owner.pubkey(), &will, MIN_TIMEOUT_SECONDS);
+    send(context, &[create, initialize], &[owner]).await.unwrap();
+    (will, rent_reserve)
+}
+
+async fn set_clock(context: &mut ProgramTestContext, unix_timestamp: i64) {
+    let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    context.warp_to_slot(clock.slot + 2).unwrap();
+    clock.slot += 2;
+    clock.unix_timestamp = unix_timestamp;
+    context.set_sysvar(&clock);
+}
 
 #[tokio::test]
-async fn test_helloworld() {
+async fn test_claim_after_release() {
     let program_id = Pubkey::new_unique();
-    let greeted_pubkey = Pubkey::new_unique();
-
-    let mut program_test = ProgramTest::new(
+    let program_test = ProgramTest::new(
         "helloworld", // Run the BPF version with `cargo test-bpf`
         program_id,
         processor!(process_instruction), // Run the native version with `cargo test`
     );
-    program_test.add_account(
-        greeted_pubkey,
-        Account {
-            lamports: 5,
-            data: vec![0_u8; mem::size_of::<u32>()],
-            owner: program_id,
-            ..Account::default()
+    let mut context = program_test.start_with_context().await;
+    let owner = Keypair::new();
+    let alice = Keypair::new();
+    let bob = Keypair::new();
+
+    // The owner creates the will at the address derived from their key and initializes it.
+    let (will, rent_reserve) = create_will(&mut context, &program_id, &owner).await;
+    let set_inheritance = instruction::set_inheritance(
+        &program_id,
+        &owner.pubkey(),
+        &will,
+        SetInheritenceMessage {
+            inheritors_names: vec![String::from("alice"), String::from("bob")],
+            inheritors_pubkeys: vec![alice.pubkey(), bob.pubkey()],
+            inheritors_shares: vec![2_500, 7_500],
+            timeout_seconds: MIN_TIMEOUT_SECONDS,
+            vesting_seconds: 0,
         },
     );
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    send(&mut context, &[set_inheritance], &[&owner]).await.unwrap();
+    let will_data = fetch_will(&mut context, will).await;
+    assert_eq!(will_data.owner, owner.pubkey());
+    assert_eq!(will_data.inheritors.len(), 2);
 
-    // Verify account has zero greetings
-    let greeted_account = banks_client
-        .get_account(greeted_pubkey)
-        .await
-        .expect("get_account")
-        .expect("greeted_account not found");
+    // Before the dead-man timeout runs out the inheritors get nothing.
+    let claim = instruction::claim(&program_id, &alice.pubkey(), &will, None, None, false);
     assert_eq!(
-        GreetingAccount::try_from_slice(&greeted_account.data)
-            .unwrap()
-            .counter,
-        0
+        send(&mut context, &[claim.clone()], &[&alice]).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(WillError::NotYetReleased as u32))
     );
 
-    // Greet once
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_bincode(
-            program_id,
-            &[0], // ignored but makes the instruction unique in the slot
-            vec![AccountMeta::new(greeted_pubkey, false)],
-        )],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[&payer], recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
+    set_clock(&mut context, will_data.withdraw_allowed_ts + 1).await;
 
-    // Verify account has one greeting
-    let greeted_account = banks_client
-        .get_account(greeted_pubkey)
-        .await
-        .expect("get_account")
-        .expect("greeted_account not found");
-    assert_eq!(
-        GreetingAccount::try_from_slice(&greeted_account.data)
-            .unwrap()
-            .counter,
-        1
+    send(&mut context, &[claim], &[&alice]).await.unwrap();
+    let balance = context.banks_client.get_balance(alice.pubkey()).await.unwrap();
+    assert_eq!(balance, (rent_reserve + ESTATE_LAMPORTS) / 4);
+    let will_data = fetch_will(&mut context, will).await;
+    assert_eq!(will_data.inheritors[0].share, 0);
+    assert_eq!(will_data.inheritors[1].share, 7_500);
+
+    // The last claim takes everything left and the emptied will is reclaimed.
+    let claim = instruction::claim(&program_id, &bob.pubkey(), &will, None, None, false);
+    send(&mut context, &[claim], &[&bob]).await.unwrap();
+    let balance = context.banks_client.get_balance(bob.pubkey()).await.unwrap();
+    assert_eq!(balance, rent_reserve + ESTATE_LAMPORTS - (rent_reserve + ESTATE_LAMPORTS) / 4);
+    assert_eq!(context.banks_client.get_account(will).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_system_program_can_not_debit_the_will() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "helloworld", // Run the BPF version with `cargo test-bpf`
+        program_id,
+        processor!(process_instruction), // Run the native version with `cargo test`
     );
+    let mut context = program_test.start_with_context().await;
+    let owner = Keypair::new();
+    let (will, rent_reserve) = create_will(&mut context, &program_id, &owner).await;
 
-    // Greet again
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_bincode(
-            program_id,
-            &[1], // ignored but makes the instruction unique in the slot
-            vec![AccountMeta::new(greeted_pubkey, false)],
-        )],
-        Some(&payer.pubkey()),
+    // Even signed for by the owner's key the will is derived from, the System Program
+    // refuses to move lamports out of an account it does not own.
+    let transfer = system_instruction::transfer_with_seed(
+        &will,
+        &owner.pubkey(),
+        WILL_SEED.to_string(),
+        &program_id,
+        &owner.pubkey(),
+        1_000,
     );
-    transaction.sign(&[&payer], recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
+    assert!(send(&mut context, &[transfer], &[&owner]).await.is_err());
+    assert_eq!(context.banks_client.get_balance(will).await.unwrap(), rent_reserve + ESTATE_LAMPORTS);
 
-    // Verify account has two greetings
-    let greeted_account = banks_client
-        .get_account(greeted_pubkey)
-        .await
-        .expect("get_account")
-        .expect("greeted_account not found");
-    assert_eq!(
-        GreetingAccount::try_from_slice(&greeted_account.data)
-            .unwrap()
-            .counter,
-        2
+    // The program itself debits the will directly, crediting a wallet it does not own.
+    let fund = system_instruction::transfer(&context.payer.pubkey(), &owner.pubkey(), ESTATE_LAMPORTS);
+    send(&mut context, &[fund], &[]).await.unwrap();
+    let withdraw = instruction::withdraw_sol(&program_id, &owner.pubkey(), &will, 1_000, true, false);
+    send(&mut context, &[withdraw], &[&owner]).await.unwrap();
+    assert_eq!(context.banks_client.get_balance(will).await.unwrap(), rent_reserve + ESTATE_LAMPORTS - 1_000);
+    assert_eq!(context.banks_client.get_balance(owner.pubkey()).await.unwrap(), ESTATE_LAMPORTS + 1_000);
+}
+
+#[tokio::test]
+async fn test_deposit() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        "helloworld", // Run the BPF version with `cargo test-bpf`
+        program_id,
+        processor!(process_instruction), // Run the native version with `cargo test`
     );
+    let mut context = program_test.start_with_context().await;
+    let owner = Keypair::new();
+    let (will, rent_reserve) = create_will(&mut context, &program_id, &owner).await;
+    let fund = system_instruction::transfer(&context.payer.pubkey(), &owner.pubkey(), 2 * ESTATE_LAMPORTS);
+    send(&mut context, &[fund], &[]).await.unwrap();
+
+    let now = fetch_will(&mut context, will).await.withdraw_allowed_ts - MIN_TIMEOUT_SECONDS + 100;
+    set_clock(&mut context, now).await;
+    let deposit = instruction::deposit(&program_id, &owner.pubkey(), &will, ESTATE_LAMPORTS);
+    send(&mut context, &[deposit], &[&owner]).await.unwrap();
+
+    let balance = context.banks_client.get_balance(will).await.unwrap();
+    assert_eq!(balance, rent_reserve + 2 * ESTATE_LAMPORTS);
+    assert_eq!(context.banks_client.get_balance(owner.pubkey()).await.unwrap(), ESTATE_LAMPORTS);
+    assert_eq!(fetch_will(&mut context, will).await.withdraw_allowed_ts, now + MIN_TIMEOUT_SECONDS);
 }
@@ -0,0 +1,143 @@
+use borsh::BorshSerialize;
+use helloworld::{find_token_authority, instruction, process_instruction, InheritorInfo, WillData, DEFAULT_TIMEOUT_SECONDS, MAX_TOTAL_SHARES, SCHEMA_VERSION};
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    program_option::COption,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+fn packed<T: Pack>(state: T) -> Vec<u8> {
+    let mut data = vec![0; T::LEN];
+    T::pack(state, &mut data).unwrap();
+    data
+}
+
+fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+    Account {
+        lamports: 1_000_000_000,
+        data: packed(TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        }),
+        owner: spl_token::id(),
+        ..Account::default()
+    }
+}
+
+async fn token_balance(banks_client: &mut BanksClient, pubkey: Pubkey) -> u64 {
+    let account = banks_client
+        .get_account(pubkey)
+        .await
+        .expect("get_account")
+        .expect("token account not found");
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_claim_tokens() {
+    let program_id = Pubkey::new_unique();
+    let will_pubkey = Pubkey::new_unique();
+    let (token_authority, _) = find_token_authority(&will_pubkey, &program_id);
+    let inheritor = Keypair::new();
+    let mint = Pubkey::new_unique();
+    let will_tokens = Pubkey::new_unique();
+    let inheritor_tokens = Pubkey::new_unique();
+
+    let mut program_test = ProgramTest::new(
+        "helloworld", // Run the BPF version with `cargo test-bpf`
+        program_id,
+        processor!(process_instruction), // Run the native version with `cargo test`
+    );
+
+    // A released will in which the inheritor holds a quarter of the shares, with the
+    // token account listed so that claiming it completes the share.
+    let will_data = WillData {
+        schema_version: SCHEMA_VERSION,
+        withdraw_allowed_ts: 0,
+        inheritors: vec![
+            InheritorInfo { name: String::from("alice"), pubkey: inheritor.pubkey(), share: 2500, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false },
+            InheritorInfo { name: String::from("bob"), pubkey: Pubkey::new_unique(), share: 7500, claimed: 0, delegate: None, fixed_amount: None, contingent_of: None, lapsed: false },
+        ],
+        timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+        owner: Pubkey::new_unique(),
+        vesting_seconds: 0,
+        frozen: false,
+        executor: None,
+        guardians: vec![],
+        required_confirmations: 0,
+        confirmations: vec![],
+        max_total_shares: MAX_TOTAL_SHARES as u32,
+        extension_votes: vec![],
+        last_heartbeat_ts: -DEFAULT_TIMEOUT_SECONDS,
+        fallback: None,
+        min_claim_interval: 0,
+        last_action_ts: 0,
+        distributable_snapshot: 0,
+        snapshot_total_shares: 0,
+        max_withdraw_per_period: None,
+        last_withdraw_ts: 0,
+        withdrawn_in_period: 0,
+        pending_withdraw_cap: None,
+        extensions: 0,
+        token_accounts: vec![will_tokens],
+    };
+    let mut data = will_data.try_to_vec().unwrap();
+    data.resize(1024, 0);
+    program_test.add_account(
+        will_pubkey,
+        Account {
+            lamports: 1_000_000_000,
+            data,
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    program_test.add_account(
+        mint,
+        Account {
+            lamports: 1_000_000_000,
+            data: packed(Mint {
+                mint_authority: COption::None,
+                supply: 1_000,
+                decimals: 0,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            }),
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    program_test.add_account(will_tokens, token_account(&mint, &token_authority, 1_000));
+    program_test.add_account(inheritor_tokens, token_account(&mint, &inheritor.pubkey(), 0));
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::claim_with_tokens(&program_id, &inheritor.pubkey(), &will_pubkey, &[(will_tokens, inheritor_tokens)])],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &inheritor], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(token_balance(&mut banks_client, will_tokens).await, 750);
+    assert_eq!(token_balance(&mut banks_client, inheritor_tokens).await, 250);
+    let inheritor_account = banks_client
+        .get_account(inheritor.pubkey())
+        .await
+        .expect("get_account")
+        .expect("inheritor account not found");
+    assert_eq!(inheritor_account.lamports, 250_000_000);
+    let will_account = banks_client
+        .get_account(will_pubkey)
+        .await
+        .expect("get_account")
+        .expect("will account not found");
+    assert_eq!(WillData::unpack(&will_account.data).unwrap().inheritors[0].share, 0);
+}